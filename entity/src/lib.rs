@@ -2,5 +2,11 @@
 
 pub mod prelude;
 
+pub mod check_runs;
+pub mod commit_statuses;
 pub mod merges;
+pub mod processed_commands;
+pub mod processed_deliveries;
 pub mod pull_requests;
+pub mod tree_states;
+pub mod webhook_deliveries;
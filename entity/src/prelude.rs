@@ -1,4 +1,10 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.6
 
+pub use super::check_runs::Entity as CheckRuns;
+pub use super::commit_statuses::Entity as CommitStatuses;
 pub use super::merges::Entity as Merges;
+pub use super::processed_commands::Entity as ProcessedCommands;
+pub use super::processed_deliveries::Entity as ProcessedDeliveries;
 pub use super::pull_requests::Entity as PullRequests;
+pub use super::tree_states::Entity as TreeStates;
+pub use super::webhook_deliveries::Entity as WebhookDeliveries;
@@ -17,6 +17,20 @@ pub enum PullRequestStatus {
     Closed,
 }
 
+/// Whether a pull request may be tested together with others in a rollup.
+#[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+#[sea_orm(rs_type = "i32", db_type = "Integer")]
+pub enum RollupMode {
+    #[sea_orm(num_value = -2)]
+    Never,
+    #[sea_orm(num_value = -1)]
+    Iffy,
+    #[sea_orm(num_value = 0)]
+    Maybe,
+    #[sea_orm(num_value = 1)]
+    Always,
+}
+
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
 #[sea_orm(table_name = "pull_requests")]
 pub struct Model {
@@ -33,9 +47,13 @@ pub struct Model {
     pub approved_by: Option<String>,
     pub priority: i32,
     pub try_test: bool,
-    pub rollup: i32,
+    pub rollup: RollupMode,
     pub squash: bool,
     pub delegate: Option<String>,
+    pub draft: bool,
+    pub approved_at: Option<DateTimeUtc>,
+    pub try_commit_id: Option<String>,
+    pub approved_sha: Option<String>,
 }
 
 // One to many relationship
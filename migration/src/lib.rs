@@ -2,6 +2,16 @@ pub use sea_orm_migration::prelude::*;
 
 mod m20240101_000001_create_pull_requests;
 mod m20240101_101620_create_merges;
+mod m20240110_000001_create_processed_deliveries;
+mod m20240111_000001_create_webhook_deliveries;
+mod m20240112_000001_add_draft_to_pull_requests;
+mod m20240113_000001_create_check_runs;
+mod m20240114_000001_create_commit_statuses;
+mod m20240115_000001_add_approved_at_to_pull_requests;
+mod m20240116_000001_add_try_commit_id_to_pull_requests;
+mod m20240117_000001_create_tree_states;
+mod m20240118_000001_create_processed_commands;
+mod m20240119_000001_add_approved_sha_to_pull_requests;
 
 pub struct Migrator;
 
@@ -11,6 +21,16 @@ impl MigratorTrait for Migrator {
         vec![
             Box::new(m20240101_000001_create_pull_requests::Migration),
             Box::new(m20240101_101620_create_merges::Migration),
+            Box::new(m20240110_000001_create_processed_deliveries::Migration),
+            Box::new(m20240111_000001_create_webhook_deliveries::Migration),
+            Box::new(m20240112_000001_add_draft_to_pull_requests::Migration),
+            Box::new(m20240113_000001_create_check_runs::Migration),
+            Box::new(m20240114_000001_create_commit_statuses::Migration),
+            Box::new(m20240115_000001_add_approved_at_to_pull_requests::Migration),
+            Box::new(m20240116_000001_add_try_commit_id_to_pull_requests::Migration),
+            Box::new(m20240117_000001_create_tree_states::Migration),
+            Box::new(m20240118_000001_create_processed_commands::Migration),
+            Box::new(m20240119_000001_add_approved_sha_to_pull_requests::Migration),
         ]
     }
 }
@@ -22,6 +42,15 @@ pub(crate) enum Merges {
     Status,
 }
 
+#[derive(DeriveIden)]
+pub(crate) enum ProcessedDeliveries {
+    Table,
+    #[sea_orm(iden = "delivery_id")]
+    DeliveryId,
+    #[sea_orm(iden = "received_at")]
+    ReceivedAt,
+}
+
 #[derive(DeriveIden)]
 pub(crate) enum PullRequests {
     Table,
@@ -46,4 +75,77 @@ pub(crate) enum PullRequests {
     Rollup,
     Squash,
     Delegate,
+    Draft,
+    #[sea_orm(iden = "approved_at")]
+    ApprovedAt,
+    #[sea_orm(iden = "try_commit_id")]
+    TryCommitId,
+    #[sea_orm(iden = "approved_sha")]
+    ApprovedSha,
+}
+
+#[derive(DeriveIden)]
+pub(crate) enum WebhookDeliveries {
+    Table,
+    Id,
+    #[sea_orm(iden = "delivery_id")]
+    DeliveryId,
+    #[sea_orm(iden = "event_type")]
+    EventType,
+    Headers,
+    Body,
+    Outcome,
+    Detail,
+    #[sea_orm(iden = "received_at")]
+    ReceivedAt,
+}
+
+#[derive(DeriveIden)]
+pub(crate) enum CheckRuns {
+    Table,
+    Repository,
+    #[sea_orm(iden = "head_sha")]
+    HeadSha,
+    Name,
+    Conclusion,
+    #[sea_orm(iden = "completed_at")]
+    CompletedAt,
+}
+
+#[derive(DeriveIden)]
+pub(crate) enum CommitStatuses {
+    Table,
+    Repository,
+    Sha,
+    Context,
+    State,
+    #[sea_orm(iden = "updated_at")]
+    UpdatedAt,
+}
+
+#[derive(DeriveIden)]
+pub(crate) enum TreeStates {
+    Table,
+    Repository,
+    Threshold,
+    #[sea_orm(iden = "updated_by")]
+    UpdatedBy,
+    #[sea_orm(iden = "updated_at")]
+    UpdatedAt,
+}
+
+#[derive(DeriveIden)]
+pub(crate) enum ProcessedCommands {
+    Table,
+    #[sea_orm(iden = "comment_kind")]
+    CommentKind,
+    #[sea_orm(iden = "comment_id")]
+    CommentId,
+    Command,
+    Repository,
+    Number,
+    Commenter,
+    Outcome,
+    #[sea_orm(iden = "processed_at")]
+    ProcessedAt,
 }
@@ -0,0 +1,48 @@
+use super::ProcessedDeliveries;
+
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // create table main.processed_deliveries
+        // (
+        //     delivery_id text not null
+        //         constraint pk_processed_deliveries
+        //             primary key,
+        //     received_at text not null
+        // );
+        manager
+            .create_table(
+                Table::create()
+                    .table(ProcessedDeliveries::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ProcessedDeliveries::DeliveryId)
+                            .text()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ProcessedDeliveries::ReceivedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .name("pk_processed_deliveries")
+                            .col(ProcessedDeliveries::DeliveryId),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ProcessedDeliveries::Table).to_owned())
+            .await
+    }
+}
@@ -0,0 +1,61 @@
+use super::WebhookDeliveries;
+
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // create table main.webhook_deliveries
+        // (
+        //     id          integer not null
+        //         constraint pk_webhook_deliveries
+        //             primary key autoincrement,
+        //     delivery_id text,
+        //     event_type  text,
+        //     headers     text    not null,
+        //     body        text    not null,
+        //     outcome     integer not null,
+        //     detail      text,
+        //     received_at text    not null
+        // );
+        manager
+            .create_table(
+                Table::create()
+                    .table(WebhookDeliveries::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(WebhookDeliveries::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(WebhookDeliveries::DeliveryId).text())
+                    .col(ColumnDef::new(WebhookDeliveries::EventType).text())
+                    .col(ColumnDef::new(WebhookDeliveries::Headers).text().not_null())
+                    .col(ColumnDef::new(WebhookDeliveries::Body).text().not_null())
+                    .col(
+                        ColumnDef::new(WebhookDeliveries::Outcome)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(WebhookDeliveries::Detail).text())
+                    .col(
+                        ColumnDef::new(WebhookDeliveries::ReceivedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(WebhookDeliveries::Table).to_owned())
+            .await
+    }
+}
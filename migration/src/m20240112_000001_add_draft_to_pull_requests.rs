@@ -0,0 +1,38 @@
+use super::PullRequests;
+
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // alter table main.pull_requests
+        //     add draft integer default 0 not null;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PullRequests::Table)
+                    .add_column(
+                        ColumnDef::new(PullRequests::Draft)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PullRequests::Table)
+                    .drop_column(PullRequests::Draft)
+                    .to_owned(),
+            )
+            .await
+    }
+}
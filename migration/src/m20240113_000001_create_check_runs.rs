@@ -0,0 +1,52 @@
+use super::CheckRuns;
+
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // create table main.check_runs
+        // (
+        //     repository   text    not null,
+        //     head_sha     text    not null,
+        //     name         text    not null,
+        //     conclusion   integer not null,
+        //     completed_at text    not null,
+        //     constraint pk_check_runs
+        //         primary key (repository, head_sha, name)
+        // );
+        manager
+            .create_table(
+                Table::create()
+                    .table(CheckRuns::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(CheckRuns::Repository).text().not_null())
+                    .col(ColumnDef::new(CheckRuns::HeadSha).text().not_null())
+                    .col(ColumnDef::new(CheckRuns::Name).text().not_null())
+                    .col(ColumnDef::new(CheckRuns::Conclusion).integer().not_null())
+                    .col(
+                        ColumnDef::new(CheckRuns::CompletedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .name("pk_check_runs")
+                            .col(CheckRuns::Repository)
+                            .col(CheckRuns::HeadSha)
+                            .col(CheckRuns::Name),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(CheckRuns::Table).to_owned())
+            .await
+    }
+}
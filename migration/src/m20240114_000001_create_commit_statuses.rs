@@ -0,0 +1,52 @@
+use super::CommitStatuses;
+
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // create table main.commit_statuses
+        // (
+        //     repository text    not null,
+        //     sha        text    not null,
+        //     context    text    not null,
+        //     state      integer not null,
+        //     updated_at text    not null,
+        //     constraint pk_commit_statuses
+        //         primary key (repository, sha, context)
+        // );
+        manager
+            .create_table(
+                Table::create()
+                    .table(CommitStatuses::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(CommitStatuses::Repository).text().not_null())
+                    .col(ColumnDef::new(CommitStatuses::Sha).text().not_null())
+                    .col(ColumnDef::new(CommitStatuses::Context).text().not_null())
+                    .col(ColumnDef::new(CommitStatuses::State).integer().not_null())
+                    .col(
+                        ColumnDef::new(CommitStatuses::UpdatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .name("pk_commit_statuses")
+                            .col(CommitStatuses::Repository)
+                            .col(CommitStatuses::Sha)
+                            .col(CommitStatuses::Context),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(CommitStatuses::Table).to_owned())
+            .await
+    }
+}
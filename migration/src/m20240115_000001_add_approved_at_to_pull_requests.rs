@@ -0,0 +1,33 @@
+use super::PullRequests;
+
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // alter table main.pull_requests
+        //     add approved_at text;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PullRequests::Table)
+                    .add_column(ColumnDef::new(PullRequests::ApprovedAt).timestamp_with_time_zone())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PullRequests::Table)
+                    .drop_column(PullRequests::ApprovedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,33 @@
+use super::PullRequests;
+
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // alter table main.pull_requests
+        //     add try_commit_id text;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PullRequests::Table)
+                    .add_column(ColumnDef::new(PullRequests::TryCommitId).string())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PullRequests::Table)
+                    .drop_column(PullRequests::TryCommitId)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,48 @@
+use super::TreeStates;
+
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // create table main.tree_states
+        // (
+        //     repository text not null
+        //         constraint pk_tree_states
+        //             primary key,
+        //     threshold  integer,
+        //     updated_by text not null,
+        //     updated_at text not null
+        // );
+        manager
+            .create_table(
+                Table::create()
+                    .table(TreeStates::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TreeStates::Repository)
+                            .text()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(TreeStates::Threshold).integer())
+                    .col(ColumnDef::new(TreeStates::UpdatedBy).text().not_null())
+                    .col(
+                        ColumnDef::new(TreeStates::UpdatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TreeStates::Table).to_owned())
+            .await
+    }
+}
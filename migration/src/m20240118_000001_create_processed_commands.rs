@@ -0,0 +1,82 @@
+use super::ProcessedCommands;
+
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // create table main.processed_commands
+        // (
+        //     comment_kind integer not null,
+        //     comment_id   integer not null,
+        //     command      text    not null,
+        //     repository   text    not null,
+        //     number       integer not null,
+        //     commenter    text    not null,
+        //     outcome      integer not null,
+        //     processed_at text    not null,
+        //     constraint pk_processed_commands
+        //         primary key (comment_kind, comment_id, command)
+        // );
+        manager
+            .create_table(
+                Table::create()
+                    .table(ProcessedCommands::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ProcessedCommands::CommentKind)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ProcessedCommands::CommentId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(ProcessedCommands::Command).text().not_null())
+                    .col(
+                        ColumnDef::new(ProcessedCommands::Repository)
+                            .text()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ProcessedCommands::Number)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ProcessedCommands::Commenter)
+                            .text()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ProcessedCommands::Outcome)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ProcessedCommands::ProcessedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .name("pk_processed_commands")
+                            .col(ProcessedCommands::CommentKind)
+                            .col(ProcessedCommands::CommentId)
+                            .col(ProcessedCommands::Command),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ProcessedCommands::Table).to_owned())
+            .await
+    }
+}
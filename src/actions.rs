@@ -1,26 +1,39 @@
-use sea_orm::{ActiveModelTrait, DbErr, EntityTrait, Set};
+use chrono::Utc;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, Set,
+};
 
 use crate::{
+    checks::forget_checks,
+    command::{help_text, Command, ParseError, Permission},
     config::get_config,
     db::get_db,
     github::{
         create_issue_comment,
-        model::{pulls::PullRequest, repo::Repository},
+        model::{
+            pulls::{PullRequest, PullRequestReviewState},
+            repo::Repository,
+            Reaction,
+        },
         GithubClient,
     },
     logging::{error, info},
-    queue::enqueue_merge,
-    routes::IssueCommentPayload,
+    permissions::{is_delegate, is_reviewer},
+    queue::{dequeue_merge, enqueue_merge, retry_merge},
+    routes::{
+        CommandContext, CommandSource, PullRequestReviewEventAction, PullRequestReviewPayload,
+    },
+    tree::set_tree_state,
 };
 
-use entity::pull_requests::PullRequestStatus;
+use entity::pull_requests::{PullRequestStatus, RollupMode};
 
 const DEFAULT_PING_MESSAGE: &str = "Hi @{{COMMENTER}}! Yes, I'm still alive!";
 const PING_MESSAGE_COMMENTER_PATTERN: &str = "{{COMMENTER}}";
-pub(crate) async fn ping(ic: &IssueCommentPayload) {
-    let owner = &ic.repository.owner.as_ref().unwrap().login;
-    let repo = &ic.repository.name;
-    let commenter = &ic.issue.user.login;
+pub(crate) async fn ping(cc: &CommandContext<'_>) -> bool {
+    let owner = &cc.repository.owner.login;
+    let repo = &cc.repository.name;
+    let commenter = &cc.author;
     let config = get_config();
     let ping_message = match config.actions {
         Some(ref a) => match a.ping {
@@ -41,21 +54,124 @@ pub(crate) async fn ping(ic: &IssueCommentPayload) {
     match create_issue_comment(
         owner,
         repo,
-        ic.issue.number,
+        cc.number,
         &ping_message.replace(PING_MESSAGE_COMMENTER_PATTERN, &commenter),
     )
     .await
     {
-        Ok(_) => {}
-        Err(e) => panic!("{e}"),
+        Ok(_) => true,
+        Err(e) => {
+            error(
+                format!("Failed to reply to ping on issue #{}. {e}", cc.number),
+                Some(&config),
+            );
+            false
+        }
+    }
+}
+
+/// Reply with the commands the bot accepts, marking the ones the commenter may use.
+pub(crate) async fn help(cc: &CommandContext<'_>, bot_name: &str) -> bool {
+    let owner = &cc.repository.owner.login;
+    let repo = &cc.repository.name;
+    let commenter = &cc.commenter;
+    let issue_number = cc.number;
+    let config = get_config();
+
+    let reviewer = is_reviewer(owner, repo, commenter).await;
+    let delegate = match get_db().await {
+        Ok(db) => find_pull_request_by_number(&db, &cc.repository.full_name, issue_number)
+            .await
+            .is_ok_and(|pr| is_delegate(pr.delegate.as_deref(), commenter)),
+        Err(_) => false,
+    };
+
+    let body = help_text(bot_name, |permission| match permission {
+        Permission::Anyone => true,
+        Permission::Reviewer => reviewer,
+        Permission::ReviewerOrDelegate => reviewer || delegate,
+    });
+
+    if let Err(e) = create_issue_comment(owner, repo, issue_number, &body).await {
+        error(
+            format!("Failed to reply to help on issue #{issue_number}. {e}"),
+            Some(&config),
+        );
+        return false;
+    }
+    true
+}
+
+/// Acknowledge the comment that issued commands with `reaction`. Github has no reactions on
+/// review bodies, so those are not acknowledged.
+pub(crate) async fn react_to_comment(cc: &CommandContext<'_>, reaction: Reaction) {
+    let owner = &cc.repository.owner.login;
+    let repo = &cc.repository.name;
+    let config = get_config();
+
+    let client = GithubClient::new(config.access_token());
+    let (result, comment_id) = match cc.source {
+        CommandSource::IssueComment { id } => (
+            client
+                .create_issue_comment_reaction(owner, repo, id, reaction)
+                .await,
+            id,
+        ),
+        CommandSource::ReviewComment { id } => (
+            client
+                .create_review_comment_reaction(owner, repo, id, reaction)
+                .await,
+            id,
+        ),
+        // Github has no reactions on reviews, and a reply for each command would bury the
+        // pull request in bot comments, so commands in a review are not acknowledged
+        CommandSource::Review { .. } => return,
+    };
+
+    if let Err(e) = result {
+        error(
+            format!(
+                "Failed to react to comment {comment_id} on #{}. {e}",
+                cc.number
+            ),
+            Some(&config),
+        );
     }
 }
 
-pub(crate) async fn set_assignee(ic: &IssueCommentPayload, assignee: Option<String>) {
-    let owner = &ic.repository.owner.as_ref().unwrap().login;
-    let repo = &ic.repository.name;
-    let commenter = &ic.issue.user.login;
-    let issue_number = ic.issue.number;
+/// Reply to a comment addressed to the bot with the parts that could not be parsed.
+pub(crate) async fn reply_parse_errors(
+    cc: &CommandContext<'_>,
+    bot_name: &str,
+    errors: &[ParseError],
+) {
+    let owner = &cc.repository.owner.login;
+    let repo = &cc.repository.name;
+    let issue_number = cc.number;
+    let config = get_config();
+
+    let errors = errors
+        .iter()
+        .map(|e| format!("- {e}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let body = format!(
+        ":grey_question: Some of the commands could not be understood:\n\n{errors}\n\nUse `@{bot_name} help` to list the commands."
+    );
+
+    if let Err(e) = create_issue_comment(owner, repo, issue_number, &body).await {
+        error(
+            format!("Failed to reply to unparsed commands on issue #{issue_number}. {e}"),
+            Some(&config),
+        );
+    }
+}
+
+pub(crate) async fn set_assignee(cc: &CommandContext<'_>, assignee: Option<String>) -> bool {
+    let owner = &cc.repository.owner.login;
+    let repo = &cc.repository.name;
+    let commenter = &cc.commenter;
+    let issue_number = cc.number;
     let config = get_config();
 
     // If the value is none, the commenter has issued the "claim" command - i.e. they are assigning themselves.
@@ -73,15 +189,17 @@ pub(crate) async fn set_assignee(ic: &IssueCommentPayload, assignee: Option<Stri
         error(
             format!("Failed to add assignee to issue #{issue_number}. Extended error: {e}"),
             Some(&config),
-        )
+        );
+        return false;
     }
+    true
 }
 
-pub(crate) async fn remove_assignee(ic: &IssueCommentPayload) {
-    let owner = &ic.repository.owner.as_ref().unwrap().login;
-    let repo = &ic.repository.name;
-    let commenter = &ic.issue.user.login;
-    let issue_number = ic.issue.number;
+pub(crate) async fn remove_assignee(cc: &CommandContext<'_>) -> bool {
+    let owner = &cc.repository.owner.login;
+    let repo = &cc.repository.name;
+    let commenter = &cc.commenter;
+    let issue_number = cc.number;
     let config = get_config();
 
     // Check that the user that issued the command is one of the assignees already
@@ -91,14 +209,21 @@ pub(crate) async fn remove_assignee(ic: &IssueCommentPayload) {
     let mut should_delete_assignee = false;
     match client.list_issue_assignees(owner, repo, issue_number).await {
         Ok(assignees) => {
-            if assignees.iter().map(|x| &x.login).any(|x| x == commenter) {
+            if assignees
+                .iter()
+                .map(|x| &x.login)
+                .any(|x| x.eq_ignore_ascii_case(commenter))
+            {
                 should_delete_assignee = true;
             }
         }
-        Err(e) => error(
-            format!("Failed to retrieve issue. Extended error: {e}"),
-            Some(&config),
-        ),
+        Err(e) => {
+            error(
+                format!("Failed to retrieve issue. Extended error: {e}"),
+                Some(&config),
+            );
+            return false;
+        }
     }
 
     if should_delete_assignee {
@@ -110,38 +235,90 @@ pub(crate) async fn remove_assignee(ic: &IssueCommentPayload) {
                 format!("Failed to delete assignee on issue #{issue_number}. Extended error: {e}"),
                 Some(&config),
             );
+            return false;
         }
     }
+    true
 }
 
-pub(crate) async fn save_pull_to_db(pr: PullRequest, repo: Repository) -> Result<(), DbErr> {
+pub(crate) async fn save_pull_to_db(pr: &PullRequest, repo: &Repository) -> Result<(), DbErr> {
     let row = entity::pull_requests::ActiveModel {
         id: Set(pr.id),
         number: Set(pr.number),
-        repository: Set(repo.full_name),
+        repository: Set(repo.full_name.clone()),
         status: Set(PullRequestStatus::Pending),
-        merge_commit_id: Set(pr.merge_commit_sha),
-        head_commit_id: Set(pr.head.sha),
-        head_ref: Set(pr.head.label.unwrap()),
-        base_ref: Set(pr.base.label.unwrap()),
-        assignee: Set(pr.assignee.map_or(None, |x| Some(x.login))),
+        merge_commit_id: Set(pr.merge_commit_sha.clone()),
+        head_commit_id: Set(pr.head.sha.clone()),
+        head_ref: Set(pr.head.label.clone()),
+        base_ref: Set(pr.base.label.clone()),
+        assignee: Set(pr.assignee.as_ref().map(|x| x.login.clone())),
         approved_by: Set(None),
         priority: Set(0),
         try_test: Set(false),
-        rollup: Set(0),
+        rollup: Set(RollupMode::Maybe),
         squash: Set(false),
         delegate: Set(None),
+        draft: Set(pr.draft.unwrap_or(false)),
+        approved_at: Set(None),
+        try_commit_id: Set(None),
+        approved_sha: Set(None),
     };
 
     row.insert(&get_db().await?).await?;
     Ok(())
 }
 
+/// Whether the bot has stored the pull request `pr_id`.
+pub(crate) async fn is_pull_request_saved(pr_id: u64) -> Result<bool, DbErr> {
+    use entity::pull_requests::Entity as PullRequests;
+
+    Ok(PullRequests::find_by_id(pr_id)
+        .one(&get_db().await?)
+        .await?
+        .is_some())
+}
+
+/// Fetch the stored pull request `pr_id`, failing with [`DbErr::RecordNotFound`] if the bot has
+/// not seen it.
+pub(crate) async fn find_pull_request(
+    db: &DatabaseConnection,
+    pr_id: u64,
+) -> Result<entity::pull_requests::Model, DbErr> {
+    use entity::pull_requests::Entity as PullRequests;
+
+    match PullRequests::find_by_id(pr_id).one(db).await? {
+        Some(pr) => Ok(pr),
+        None => Err(DbErr::RecordNotFound(format!(
+            "No pull request with id {pr_id}"
+        ))),
+    }
+}
+
+/// Fetch the stored pull request numbered `number` in the `owner/repo` repository.
+pub(crate) async fn find_pull_request_by_number(
+    db: &DatabaseConnection,
+    repository: &str,
+    number: u64,
+) -> Result<entity::pull_requests::Model, DbErr> {
+    use entity::pull_requests::{Column, Entity as PullRequests};
+
+    match PullRequests::find()
+        .filter(Column::Repository.eq(repository))
+        .filter(Column::Number.eq(number))
+        .one(db)
+        .await?
+    {
+        Some(pr) => Ok(pr),
+        None => Err(DbErr::RecordNotFound(format!(
+            "No pull request #{number} in {repository}"
+        ))),
+    }
+}
+
 pub(crate) async fn set_pull_request_status(
     pr_id: u64,
     status: PullRequestStatus,
 ) -> Result<(), DbErr> {
-    use entity::pull_requests::Entity as PullRequests;
     let db = get_db().await?;
     //     let pear: Option<fruit::Model> = Fruit::find_by_id(28).one(db).await?;
 
@@ -154,8 +331,7 @@ pub(crate) async fn set_pull_request_status(
     // // SQL: `UPDATE "fruit" SET "name" = 'Sweet pear' WHERE "id" = 28`
     // let pear: fruit::Model = pear.update(db).await?;
 
-    let pr: Option<entity::pull_requests::Model> = PullRequests::find_by_id(pr_id).one(&db).await?;
-    let mut pr: entity::pull_requests::ActiveModel = pr.unwrap().into();
+    let mut pr: entity::pull_requests::ActiveModel = find_pull_request(&db, pr_id).await?.into();
 
     pr.status = Set(status);
 
@@ -185,17 +361,31 @@ pub(crate) async fn set_pull_request_status(
     //         .await
 }
 
+/// Mark the pull request closed. A pull request the queue landed by squashing or rebasing is
+/// closed rather than merged on Github, and stays marked as merged.
+pub(crate) async fn set_pull_request_closed(pr_id: u64) -> Result<(), DbErr> {
+    let db = get_db().await?;
+
+    if find_pull_request(&db, pr_id).await?.status == PullRequestStatus::Merged {
+        return Ok(());
+    }
+
+    set_pull_request_status(pr_id, PullRequestStatus::Closed).await
+}
+
+/// Record the approval of the pull request's head commit `approved_sha` by `approved_by`.
 pub(crate) async fn set_pull_request_approved(
     pr_id: u64,
     approved_by: String,
+    approved_sha: String,
 ) -> Result<(), DbErr> {
-    use entity::pull_requests::Entity as PullRequests;
     let db = get_db().await?;
 
-    let pr: Option<entity::pull_requests::Model> = PullRequests::find_by_id(pr_id).one(&db).await?;
-    let mut pr: entity::pull_requests::ActiveModel = pr.unwrap().into();
+    let mut pr: entity::pull_requests::ActiveModel = find_pull_request(&db, pr_id).await?.into();
 
     pr.approved_by = Set(Some(approved_by));
+    pr.approved_at = Set(Some(Utc::now()));
+    pr.approved_sha = Set(Some(approved_sha));
 
     match pr.update(&db).await {
         Ok(_) => Ok(()),
@@ -203,27 +393,640 @@ pub(crate) async fn set_pull_request_approved(
     }
 }
 
-pub(crate) async fn approve_pull(ic: &IssueCommentPayload) {
-    let owner = &ic.repository.owner.as_ref().unwrap().login;
-    let repo = &ic.repository.name;
-    let commenter = &ic.issue.user.login;
-    let issue_number = ic.issue.number;
+pub(crate) async fn set_pull_request_priority(pr_id: u64, priority: i32) -> Result<(), DbErr> {
+    let db = get_db().await?;
+
+    let mut pr: entity::pull_requests::ActiveModel = find_pull_request(&db, pr_id).await?.into();
+    pr.priority = Set(priority);
+    pr.update(&db).await?;
+    Ok(())
+}
 
+pub(crate) async fn set_pull_request_rollup(pr_id: u64, rollup: RollupMode) -> Result<(), DbErr> {
+    let db = get_db().await?;
+
+    let mut pr: entity::pull_requests::ActiveModel = find_pull_request(&db, pr_id).await?.into();
+    pr.rollup = Set(rollup);
+    pr.update(&db).await?;
+    Ok(())
+}
+
+pub(crate) async fn set_pull_request_delegate(
+    pr_id: u64,
+    delegate: Option<String>,
+) -> Result<(), DbErr> {
+    let db = get_db().await?;
+
+    let mut pr: entity::pull_requests::ActiveModel = find_pull_request(&db, pr_id).await?.into();
+    pr.delegate = Set(delegate);
+    pr.update(&db).await?;
+    Ok(())
+}
+
+pub(crate) async fn set_pull_request_squash(pr_id: u64, squash: bool) -> Result<(), DbErr> {
+    let db = get_db().await?;
+
+    let mut pr: entity::pull_requests::ActiveModel = find_pull_request(&db, pr_id).await?.into();
+    pr.squash = Set(squash);
+    pr.update(&db).await?;
+    Ok(())
+}
+
+/// Request a try build of the pull request, or mark a try build as finished. Any earlier try
+/// commit is forgotten, so a repeated request starts a new try build.
+pub(crate) async fn set_pull_request_try(pr_id: u64, try_test: bool) -> Result<(), DbErr> {
+    let db = get_db().await?;
+
+    let mut pr: entity::pull_requests::ActiveModel = find_pull_request(&db, pr_id).await?.into();
+    pr.try_test = Set(try_test);
+    pr.try_commit_id = Set(None);
+    pr.update(&db).await?;
+    Ok(())
+}
+
+/// Record the merge commit created for the pull request's try build.
+pub(crate) async fn set_pull_request_try_commit(
+    pr_id: u64,
+    try_commit_id: &str,
+) -> Result<(), DbErr> {
+    let db = get_db().await?;
+
+    let mut pr: entity::pull_requests::ActiveModel = find_pull_request(&db, pr_id).await?.into();
+    pr.try_commit_id = Set(Some(try_commit_id.into()));
+    pr.update(&db).await?;
+    Ok(())
+}
+
+/// Forget everything about the pull request's merge and try builds: the merge queue row, the
+/// test merge commits and the checks recorded against them. An approved pull request goes back
+/// in the queue to be tested from scratch.
+pub(crate) async fn clean_pull_request(pr_id: u64) -> Result<(), DbErr> {
+    let db = get_db().await?;
+
+    let model = find_pull_request(&db, pr_id).await?;
+    for sha in [&model.merge_commit_id, &model.try_commit_id]
+        .into_iter()
+        .flatten()
+    {
+        forget_checks(&model.repository, sha).await?;
+    }
+
+    dequeue_merge(pr_id).await?;
+
+    let approved = model.approved_by.is_some();
+    let draft = model.draft;
+
+    let mut pr: entity::pull_requests::ActiveModel = model.into();
+    pr.merge_commit_id = Set(None);
+    pr.try_test = Set(false);
+    pr.try_commit_id = Set(None);
+    pr.update(&db).await?;
+
+    if approved && !draft {
+        enqueue_merge(pr_id).await?;
+    }
+    Ok(())
+}
+
+/// Record a new head commit for the pull request. Any approval was given for the old head, so
+/// it is cleared and the pull request is taken out of the merge queue.
+///
+/// Returns `true` if an approval was reset.
+pub(crate) async fn set_pull_request_head(
+    pr_id: u64,
+    head_commit_id: String,
+) -> Result<bool, DbErr> {
+    let db = get_db().await?;
+
+    let mut pr: entity::pull_requests::ActiveModel = find_pull_request(&db, pr_id).await?.into();
+    pr.head_commit_id = Set(head_commit_id);
+    pr.update(&db).await?;
+
+    clear_pull_request_approval(pr_id).await
+}
+
+/// Tell the pull request `number` that a push of `head_sha` has reset its approval.
+pub(crate) async fn comment_approval_reset(repository: &Repository, number: u64, head_sha: &str) {
+    let owner = &repository.owner.login;
+    let repo = &repository.name;
+    let body = format!(
+        ":warning: Approval reset: {head_sha} was pushed after this pull request was approved. It needs to be approved again."
+    );
+
+    if let Err(e) = create_issue_comment(owner, repo, number, &body).await {
+        error(
+            format!("Failed to comment on the approval reset of pull request #{number}. {e}"),
+            Some(&get_config()),
+        );
+    }
+}
+
+/// Record the merge commit created to test the pull request.
+pub(crate) async fn set_pull_request_merge_commit(
+    pr_id: u64,
+    merge_commit_id: &str,
+) -> Result<(), DbErr> {
+    let db = get_db().await?;
+
+    let mut pr: entity::pull_requests::ActiveModel = find_pull_request(&db, pr_id).await?.into();
+    pr.merge_commit_id = Set(Some(merge_commit_id.into()));
+    pr.update(&db).await?;
+    Ok(())
+}
+
+/// Clear the pull request's approval and remove it from the merge queue.
+///
+/// Returns `true` if there was an approval to clear.
+pub(crate) async fn clear_pull_request_approval(pr_id: u64) -> Result<bool, DbErr> {
+    let db = get_db().await?;
+
+    let model = find_pull_request(&db, pr_id).await?;
+    let was_approved = model.approved_by.is_some();
+
+    let mut pr: entity::pull_requests::ActiveModel = model.into();
+    pr.approved_by = Set(None);
+    pr.approved_at = Set(None);
+    pr.approved_sha = Set(None);
+    pr.update(&db).await?;
+
+    dequeue_merge(pr_id).await?;
+    Ok(was_approved)
+}
+
+/// Track the pull request's base branch, which changes when the pull request is retargeted.
+pub(crate) async fn set_pull_request_base(pr_id: u64, base_ref: String) -> Result<(), DbErr> {
+    let db = get_db().await?;
+
+    let mut pr: entity::pull_requests::ActiveModel = find_pull_request(&db, pr_id).await?.into();
+    pr.base_ref = Set(base_ref);
+    pr.update(&db).await?;
+    Ok(())
+}
+
+/// Mark the pull request as a draft or as ready for review. Drafts cannot be in the merge
+/// queue, so converting to a draft removes any queued merge, and marking an approved pull
+/// request as ready puts it back in the queue.
+pub(crate) async fn set_pull_request_draft(pr_id: u64, draft: bool) -> Result<(), DbErr> {
+    let db = get_db().await?;
+
+    let model = find_pull_request(&db, pr_id).await?;
+    let approved = model.approved_by.is_some();
+
+    let mut pr: entity::pull_requests::ActiveModel = model.into();
+    pr.draft = Set(draft);
+    pr.update(&db).await?;
+
+    if draft {
+        dequeue_merge(pr_id).await
+    } else if approved {
+        enqueue_merge(pr_id).await
+    } else {
+        Ok(())
+    }
+}
+
+pub(crate) async fn set_priority(cc: &CommandContext<'_>, priority: i32) -> bool {
+    let repository = &cc.repository.full_name;
+    let issue_number = cc.number;
+    let config = get_config();
+
+    let result = match get_db().await {
+        Ok(db) => match find_pull_request_by_number(&db, repository, issue_number).await {
+            Ok(pr) => set_pull_request_priority(pr.id, priority).await,
+            Err(e) => Err(e),
+        },
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(_) => {
+            info(
+                format!("Set priority of pull request #{issue_number} to {priority}"),
+                Some(&config),
+            );
+            true
+        }
+        Err(e) => {
+            error(
+                format!("Failed to set priority of pull request #{issue_number}. {e}"),
+                Some(&config),
+            );
+            false
+        }
+    }
+}
+
+pub(crate) async fn set_rollup(cc: &CommandContext<'_>, rollup: RollupMode) -> bool {
+    let repository = &cc.repository.full_name;
+    let issue_number = cc.number;
+    let config = get_config();
+
+    let result = match get_db().await {
+        Ok(db) => match find_pull_request_by_number(&db, repository, issue_number).await {
+            Ok(pr) => set_pull_request_rollup(pr.id, rollup.clone()).await,
+            Err(e) => Err(e),
+        },
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(_) => {
+            info(
+                format!("Set rollup of pull request #{issue_number} to {rollup:?}"),
+                Some(&config),
+            );
+            true
+        }
+        Err(e) => {
+            error(
+                format!("Failed to set rollup of pull request #{issue_number}. {e}"),
+                Some(&config),
+            );
+            false
+        }
+    }
+}
+
+pub(crate) async fn set_squash(cc: &CommandContext<'_>, squash: bool) -> bool {
+    let repository = &cc.repository.full_name;
+    let issue_number = cc.number;
+    let config = get_config();
+
+    let result = match get_db().await {
+        Ok(db) => match find_pull_request_by_number(&db, repository, issue_number).await {
+            Ok(pr) => set_pull_request_squash(pr.id, squash).await,
+            Err(e) => Err(e),
+        },
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(_) => {
+            info(
+                format!("Set squash of pull request #{issue_number} to {squash}"),
+                Some(&config),
+            );
+            true
+        }
+        Err(e) => {
+            error(
+                format!("Failed to set squash of pull request #{issue_number}. {e}"),
+                Some(&config),
+            );
+            false
+        }
+    }
+}
+
+/// Close or reopen the repository's tree and announce the change. A `threshold` of `None` opens
+/// the tree.
+pub(crate) async fn set_tree_closed(cc: &CommandContext<'_>, threshold: Option<i32>) -> bool {
+    let owner = &cc.repository.owner.login;
+    let repo = &cc.repository.name;
+    let commenter = &cc.commenter;
+    let issue_number = cc.number;
+    let config = get_config();
+
+    if let Err(e) = set_tree_state(&cc.repository.full_name, threshold, commenter).await {
+        error(
+            format!("Failed to save tree state for {owner}/{repo}. {e}"),
+            Some(&config),
+        );
+        return false;
+    }
+
+    let body = match threshold {
+        Some(t) => format!(
+            ":no_entry: Tree closed by `{commenter}` for pull requests with priority below {t}."
+        ),
+        None => format!(":white_check_mark: Tree opened by `{commenter}`."),
+    };
+    if let Err(e) = create_issue_comment(owner, repo, issue_number, &body).await {
+        error(
+            format!("Failed to create issue comment for tree state. {e}"),
+            Some(&config),
+        );
+    }
+    true
+}
+
+/// Put the pull request's failed merge back in the queue in response to `retry`.
+pub(crate) async fn retry_pull(cc: &CommandContext<'_>) -> bool {
+    let owner = &cc.repository.owner.login;
+    let repo = &cc.repository.name;
+    let issue_number = cc.number;
+    let config = get_config();
+
+    let retried = match get_db().await {
+        Ok(db) => {
+            match find_pull_request_by_number(&db, &cc.repository.full_name, issue_number).await {
+                Ok(pr) => retry_merge(pr.id).await,
+                Err(e) => Err(e),
+            }
+        }
+        Err(e) => Err(e),
+    };
+
+    let (retried, body) = match retried {
+        Ok(true) => (true, ":repeat: The merge will be retried."),
+        Ok(false) => (false, ":grey_question: There is no failed merge to retry."),
+        Err(e) => {
+            error(
+                format!("Failed to retry merge of pull request #{issue_number}. {e}"),
+                Some(&config),
+            );
+            return false;
+        }
+    };
+
+    if let Err(e) = create_issue_comment(owner, repo, issue_number, body).await {
+        error(
+            format!("Failed to create issue comment for retry. {e}"),
+            Some(&config),
+        );
+    }
+    retried
+}
+
+/// Clear the pull request's test state in response to `clean`.
+pub(crate) async fn clean_pull(cc: &CommandContext<'_>) -> bool {
+    let owner = &cc.repository.owner.login;
+    let repo = &cc.repository.name;
+    let issue_number = cc.number;
+    let config = get_config();
+
+    let result = match get_db().await {
+        Ok(db) => {
+            match find_pull_request_by_number(&db, &cc.repository.full_name, issue_number).await {
+                Ok(pr) => clean_pull_request(pr.id).await,
+                Err(e) => Err(e),
+            }
+        }
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = result {
+        error(
+            format!("Failed to clean pull request #{issue_number}. {e}"),
+            Some(&config),
+        );
+        return false;
+    }
+
+    if let Err(e) = create_issue_comment(
+        owner,
+        repo,
+        issue_number,
+        ":broom: Cleared the merge and try build state of this pull request.",
+    )
+    .await
+    {
+        error(
+            format!("Failed to create issue comment for clean. {e}"),
+            Some(&config),
+        );
+    }
+    true
+}
+
+pub(crate) async fn request_try(cc: &CommandContext<'_>) -> bool {
+    let repository = &cc.repository.full_name;
+    let issue_number = cc.number;
+    let config = get_config();
+
+    let result = match get_db().await {
+        Ok(db) => match find_pull_request_by_number(&db, repository, issue_number).await {
+            Ok(pr) => set_pull_request_try(pr.id, true).await,
+            Err(e) => Err(e),
+        },
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(_) => {
+            info(
+                format!("Requested a try build of pull request #{issue_number}"),
+                Some(&config),
+            );
+            true
+        }
+        Err(e) => {
+            error(
+                format!("Failed to request a try build of pull request #{issue_number}. {e}"),
+                Some(&config),
+            );
+            false
+        }
+    }
+}
+
+/// Reply to a command the commenter is not allowed to issue.
+async fn refuse_command(cc: &CommandContext<'_>, body: &str) {
+    let owner = &cc.repository.owner.login;
+    let repo = &cc.repository.name;
+    let config = get_config();
+
+    info(
+        format!("Refused command from @{} on #{}", cc.commenter, cc.number),
+        Some(&config),
+    );
+
+    if let Err(e) = create_issue_comment(owner, repo, cc.number, body).await {
+        error(
+            format!("Failed to create issue comment for refused command. {e}"),
+            Some(&config),
+        );
+    }
+}
+
+/// Decide whether the commenter may issue `command`, replying with an explanation if not.
+pub(crate) async fn authorize_command(cc: &CommandContext<'_>, command: &Command) -> bool {
+    let owner = &cc.repository.owner.login;
+    let repo = &cc.repository.name;
+    let commenter = &cc.commenter;
+    let issue_number = cc.number;
+    let config = get_config();
+
+    let permission = command.permission();
+    if permission == Permission::Anyone || is_reviewer(owner, repo, commenter).await {
+        return true;
+    }
+
+    if permission == Permission::ReviewerOrDelegate {
+        let pr = match get_db().await {
+            Ok(db) => {
+                find_pull_request_by_number(&db, &cc.repository.full_name, issue_number).await
+            }
+            Err(e) => Err(e),
+        };
+
+        match pr {
+            Ok(pr) if is_delegate(pr.delegate.as_deref(), commenter) => return true,
+            Ok(_) => {}
+            Err(e) => error(
+                format!("Failed to find pull request #{issue_number} to check delegation. {e}"),
+                Some(&config),
+            ),
+        }
+    }
+
+    refuse_command(
+        cc,
+        &format!(
+            ":key: @{commenter} does not have review rights on this repository, so cannot use `{}`.",
+            command.name()
+        ),
+    )
+    .await;
+    false
+}
+
+/// The head of the pull request as last seen by the bot, which is the commit a comment approving
+/// it was written against.
+async fn reviewed_head(cc: &CommandContext<'_>) -> Option<String> {
+    let db = match get_db().await {
+        Ok(db) => db,
+        Err(e) => {
+            error(format!("Failed to open database. {e}"), Some(&get_config()));
+            return None;
+        }
+    };
+
+    match find_pull_request_by_number(&db, &cc.repository.full_name, cc.number).await {
+        Ok(pr) => Some(pr.head_commit_id),
+        Err(e) => {
+            error(
+                format!("Failed to retrieve pull request #{}. {e}", cc.number),
+                Some(&get_config()),
+            );
+            None
+        }
+    }
+}
+
+/// Approve the pull request in response to `r+`.
+pub(crate) async fn approve_pull(cc: &CommandContext<'_>) -> bool {
+    let owner = &cc.repository.owner.login;
+    let repo = &cc.repository.name;
+    let commenter = &cc.commenter;
+
+    match reviewed_head(cc).await {
+        Some(sha) => approve_pull_request(owner, repo, cc.number, commenter, &sha).await,
+        None => false,
+    }
+}
+
+/// Approve the pull request on behalf of `reviewer`.
+pub(crate) async fn approve_pull_as(cc: &CommandContext<'_>, reviewer: &str) -> bool {
+    let owner = &cc.repository.owner.login;
+    let repo = &cc.repository.name;
+
+    match reviewed_head(cc).await {
+        Some(sha) => approve_pull_request(owner, repo, cc.number, reviewer, &sha).await,
+        None => false,
+    }
+}
+
+/// Grant `delegate` the right to approve this pull request, or revoke the delegation if
+/// `delegate` is `None`.
+pub(crate) async fn delegate_pull(cc: &CommandContext<'_>, delegate: Option<String>) -> bool {
+    let owner = &cc.repository.owner.login;
+    let repo = &cc.repository.name;
+    let issue_number = cc.number;
+    let config = get_config();
+
+    let result = match get_db().await {
+        Ok(db) => {
+            match find_pull_request_by_number(&db, &cc.repository.full_name, issue_number).await {
+                Ok(pr) => set_pull_request_delegate(pr.id, delegate.clone()).await,
+                Err(e) => Err(e),
+            }
+        }
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = result {
+        error(
+            format!("Failed to save delegation for pull request #{issue_number}. {e}"),
+            Some(&config),
+        );
+        return false;
+    }
+
+    let body = match &delegate {
+        Some(d) => format!(":v: @{d} can now approve this pull request."),
+        None => ":v: Delegation removed.".to_string(),
+    };
+    if let Err(e) = create_issue_comment(owner, repo, issue_number, &body).await {
+        error(
+            format!("Failed to create issue comment for delegation. {e}"),
+            Some(&config),
+        );
+    }
+    true
+}
+
+/// Remove the pull request's approval in response to `r-`.
+pub(crate) async fn unapprove_pull(cc: &CommandContext<'_>) -> bool {
+    let owner = &cc.repository.owner.login;
+    let repo = &cc.repository.name;
+    let commenter = &cc.commenter;
+    let issue_number = cc.number;
+    let config = get_config();
+
+    let pr = match get_db().await {
+        Ok(db) => find_pull_request_by_number(&db, &cc.repository.full_name, issue_number).await,
+        Err(e) => Err(e),
+    };
+
+    match pr {
+        Ok(pr) => {
+            unapprove_pull_request(
+                owner,
+                repo,
+                issue_number,
+                pr.id,
+                &format!("Unapproved by `{commenter}`."),
+            )
+            .await
+        }
+        Err(e) => {
+            error(
+                format!("Failed to find pull request #{issue_number} to unapprove. {e}"),
+                Some(&config),
+            );
+            false
+        }
+    }
+}
+
+/// Record `approver`'s approval of the commit `reviewed_sha`, add the pull request to the merge
+/// queue and report the approval as an issue comment.
+///
+/// The approval is refused if the head of the pull request has moved on from `reviewed_sha`, as
+/// the new commits have not been reviewed.
+pub(crate) async fn approve_pull_request(
+    owner: &str,
+    repo: &str,
+    issue_number: u64,
+    approver: &str,
+    reviewed_sha: &str,
+) -> bool {
     let config = get_config();
 
     let client = GithubClient::new(config.access_token());
-    let (pull_number, commit_id, pull_id) = match client
+    let (pull_number, commit_id, pull_id, draft) = match client
         .get_pull_request_from_issue_number(owner, repo, issue_number)
         .await
     {
         Ok(inner) => match inner {
-            Some(pr) => (pr.number, pr.head.sha, pr.id),
+            Some(pr) => (pr.number, pr.head.sha, pr.id, pr.draft.unwrap_or(false)),
             None => {
                 error(
                     format!("No pull request for issue #{issue_number}"),
                     Some(&config),
                 );
-                return;
+                return false;
             }
         },
         Err(e) => {
@@ -231,31 +1034,68 @@ pub(crate) async fn approve_pull(ic: &IssueCommentPayload) {
                 format!("Failed to retrieve pull request for issue #{issue_number}. {e}"),
                 Some(&config),
             );
-            return;
+            return false;
         }
     };
 
-    if let Err(e) = set_pull_request_approved(pull_id, commenter.clone()).await {
+    if draft {
+        if let Err(e) = client
+            .create_issue_comment(
+                owner,
+                repo,
+                issue_number,
+                ":pencil2: This pull request is a draft. Mark it as ready for review before approving it.",
+            )
+            .await
+        {
+            error(
+                format!("Failed to create issue comment for draft pull. {e}"),
+                Some(&config),
+            );
+        }
+        return false;
+    }
+
+    if commit_id != reviewed_sha {
+        let body = format!(
+            ":warning: Approval refused: {commit_id} was pushed after {reviewed_sha} was reviewed. Review the new commits and approve again."
+        );
+        if let Err(e) = client
+            .create_issue_comment(owner, repo, issue_number, &body)
+            .await
+        {
+            error(
+                format!("Failed to create issue comment for refused approval. {e}"),
+                Some(&config),
+            );
+        }
+        return false;
+    }
+
+    if let Err(e) =
+        set_pull_request_approved(pull_id, approver.to_string(), commit_id.clone()).await
+    {
         error(
             format!("Failed to save approved status for pull request #{pull_number}. {e}"),
             Some(&config),
         );
-        return;
+        return false;
     }
 
-    match enqueue_merge(pull_id).await {
-        Ok(_) => {}
+    let queued = match enqueue_merge(pull_id).await {
+        Ok(_) => true,
         Err(e) => {
             error(
                 format!("Failed to add approved review to pull request #{pull_number}. {e}"),
                 Some(&config),
             );
+            false
         }
-    }
+    };
 
     let body = format!(
         r"
-:pushpin: Commit {commit_id} has been approved by `{commenter}`
+:pushpin: Commit {commit_id} has been approved by `{approver}`
 
 It is now in the queue for this repository."
     );
@@ -271,4 +1111,129 @@ It is now in the queue for this repository."
             );
         }
     }
+    queued
+}
+
+/// Remove the pull request's approval and take it out of the merge queue, reporting `reason`
+/// as an issue comment if there was an approval to remove.
+pub(crate) async fn unapprove_pull_request(
+    owner: &str,
+    repo: &str,
+    pull_number: u64,
+    pull_id: u64,
+    reason: &str,
+) -> bool {
+    let config = get_config();
+
+    let was_approved = match clear_pull_request_approval(pull_id).await {
+        Ok(a) => a,
+        Err(e) => {
+            error(
+                format!("Failed to remove approval for pull request #{pull_number}. {e}"),
+                Some(&config),
+            );
+            return false;
+        }
+    };
+
+    if !was_approved {
+        return false;
+    }
+
+    let body = format!(":x: Approval removed. {reason}");
+    let client = GithubClient::new(config.access_token());
+    if let Err(e) = client
+        .create_issue_comment(owner, repo, pull_number, &body)
+        .await
+    {
+        error(
+            format!("Failed to create issue comment for unapproved pull. {e}"),
+            Some(&config),
+        );
+    }
+    true
+}
+
+/// Treat Github-native reviews as approval commands, for repositories that have opted in with
+/// `review_approvals`. An approving review behaves like `r+`, while a review requesting changes
+/// or the dismissal of the approving review removes the approval.
+///
+/// Returns `true` if the review's state approved or unapproved the pull request, in which case
+/// approval commands in the review's body must not be run as well.
+pub(crate) async fn handle_pull_request_review(payload: &PullRequestReviewPayload) -> bool {
+    let config = get_config();
+    let owner = &payload.repository.owner.login;
+    let repo = &payload.repository.name;
+    let reviewer = &payload.review.user.login;
+    let pull_number = payload.pull_request.number;
+
+    match config.repo(owner, repo) {
+        Some(r) if r.review_approvals() => {}
+        _ => return false,
+    }
+
+    if !is_reviewer(owner, repo, reviewer).await {
+        info(
+            format!("Ignoring review on #{pull_number} from unauthorized reviewer @{reviewer}"),
+            Some(&config),
+        );
+        return false;
+    }
+
+    match (&payload.action, &payload.review.state) {
+        (PullRequestReviewEventAction::Submitted, PullRequestReviewState::Approved) => {
+            approve_pull_request(
+                owner,
+                repo,
+                pull_number,
+                reviewer,
+                &payload.review.commit_id,
+            )
+            .await;
+            true
+        }
+        (PullRequestReviewEventAction::Submitted, PullRequestReviewState::ChangesRequested) => {
+            unapprove_pull_request(
+                owner,
+                repo,
+                pull_number,
+                payload.pull_request.id,
+                &format!("`{reviewer}` has requested changes."),
+            )
+            .await;
+            true
+        }
+        (PullRequestReviewEventAction::Dismissed, _) => {
+            // Only the dismissal of the review that approved the pull request withdraws it
+            let approved_by = match get_db().await {
+                Ok(db) => match find_pull_request(&db, payload.pull_request.id).await {
+                    Ok(pr) => pr.approved_by,
+                    Err(e) => {
+                        error(
+                            format!("Failed to retrieve pull request #{pull_number}. {e}"),
+                            Some(&config),
+                        );
+                        return false;
+                    }
+                },
+                Err(e) => {
+                    error(format!("Failed to open database. {e}"), Some(&config));
+                    return false;
+                }
+            };
+
+            if approved_by.as_ref() == Some(reviewer) {
+                unapprove_pull_request(
+                    owner,
+                    repo,
+                    pull_number,
+                    payload.pull_request.id,
+                    &format!("The review from `{reviewer}` was dismissed."),
+                )
+                .await;
+            }
+            false
+        }
+        _ => false,
+    }
 }
@@ -0,0 +1,370 @@
+use chrono::Utc;
+use entity::{
+    check_runs::{
+        ActiveModel as CheckRunActiveModel, CheckConclusion, Column as CheckRunsColumn,
+        Entity as CheckRunsEntity,
+    },
+    commit_statuses::{
+        ActiveModel as CommitStatusActiveModel, Column as CommitStatusesColumn, CommitStatusState,
+        Entity as CommitStatusesEntity,
+    },
+};
+use sea_orm::{sea_query::OnConflict, ColumnTrait, DbErr, EntityTrait, QueryFilter, Set};
+
+use crate::{
+    db::get_db,
+    github::model::{checks::CheckSuiteConclusion, statuses::StatusState},
+};
+
+impl From<&CheckSuiteConclusion> for CheckConclusion {
+    fn from(value: &CheckSuiteConclusion) -> Self {
+        match value {
+            CheckSuiteConclusion::Success => CheckConclusion::Success,
+            CheckSuiteConclusion::Failure => CheckConclusion::Failure,
+            CheckSuiteConclusion::Neutral => CheckConclusion::Neutral,
+            CheckSuiteConclusion::Cancelled => CheckConclusion::Cancelled,
+            CheckSuiteConclusion::TimedOut => CheckConclusion::TimedOut,
+            CheckSuiteConclusion::ActionRequired => CheckConclusion::ActionRequired,
+            CheckSuiteConclusion::Stale => CheckConclusion::Stale,
+            CheckSuiteConclusion::Skipped => CheckConclusion::Skipped,
+            CheckSuiteConclusion::StartupFailure => CheckConclusion::StartupFailure,
+        }
+    }
+}
+
+impl From<&StatusState> for CommitStatusState {
+    fn from(value: &StatusState) -> Self {
+        match value {
+            StatusState::Pending => CommitStatusState::Pending,
+            StatusState::Success => CommitStatusState::Success,
+            StatusState::Failure => CommitStatusState::Failure,
+            StatusState::Error => CommitStatusState::Error,
+        }
+    }
+}
+
+/// The conclusion as Github reports it, for use in comments.
+pub(crate) fn describe_conclusion(conclusion: &CheckConclusion) -> &'static str {
+    match conclusion {
+        CheckConclusion::Success => "success",
+        CheckConclusion::Failure => "failure",
+        CheckConclusion::Neutral => "neutral",
+        CheckConclusion::Cancelled => "cancelled",
+        CheckConclusion::TimedOut => "timed_out",
+        CheckConclusion::ActionRequired => "action_required",
+        CheckConclusion::Stale => "stale",
+        CheckConclusion::Skipped => "skipped",
+        CheckConclusion::StartupFailure => "startup_failure",
+    }
+}
+
+/// The combined result of the checks and commit statuses a repository requires before merging
+/// a commit.
+#[derive(Debug, PartialEq)]
+pub(crate) enum ChecksState {
+    /// At least one required check has not concluded successfully yet.
+    Pending,
+
+    /// Every required check concluded `success`.
+    Succeeded,
+
+    /// A required check concluded `failure`, `timed_out` or `cancelled`, or a required status
+    /// reached `failure` or `error`. `conclusion` is the name Github reports it by.
+    Failed { name: String, conclusion: String },
+}
+
+impl ChecksState {
+    /// Combine two sets of gates. A failure in either fails both, and both must succeed for the
+    /// combination to succeed.
+    pub(crate) fn and(self, other: ChecksState) -> ChecksState {
+        match (self, other) {
+            (failed @ ChecksState::Failed { .. }, _) | (_, failed @ ChecksState::Failed { .. }) => {
+                failed
+            }
+            (ChecksState::Succeeded, ChecksState::Succeeded) => ChecksState::Succeeded,
+            _ => ChecksState::Pending,
+        }
+    }
+}
+
+/// Record the conclusion of a completed check against the commit it ran on. A re-run check
+/// replaces the earlier conclusion.
+pub(crate) async fn record_check_run(
+    repository: &str,
+    head_sha: &str,
+    name: &str,
+    conclusion: CheckConclusion,
+) -> Result<(), DbErr> {
+    let row = CheckRunActiveModel {
+        repository: Set(repository.into()),
+        head_sha: Set(head_sha.into()),
+        name: Set(name.into()),
+        conclusion: Set(conclusion),
+        completed_at: Set(Utc::now()),
+    };
+
+    CheckRunsEntity::insert(row)
+        .on_conflict(
+            OnConflict::columns([
+                CheckRunsColumn::Repository,
+                CheckRunsColumn::HeadSha,
+                CheckRunsColumn::Name,
+            ])
+            .update_columns([CheckRunsColumn::Conclusion, CheckRunsColumn::CompletedAt])
+            .to_owned(),
+        )
+        .exec_without_returning(&get_db().await?)
+        .await?;
+    Ok(())
+}
+
+/// Look up the recorded conclusions for `head_sha` and combine them for the `required` checks.
+pub(crate) async fn get_checks_state(
+    repository: &str,
+    head_sha: &str,
+    required: &[String],
+) -> Result<ChecksState, DbErr> {
+    let completed = CheckRunsEntity::find()
+        .filter(CheckRunsColumn::Repository.eq(repository))
+        .filter(CheckRunsColumn::HeadSha.eq(head_sha))
+        .all(&get_db().await?)
+        .await?
+        .into_iter()
+        .map(|c| (c.name, c.conclusion))
+        .collect::<Vec<_>>();
+
+    Ok(evaluate_checks(required, &completed))
+}
+
+/// Combine completed check conclusions for the `required` checks. A failure in any required
+/// check fails the whole set, even if other checks are still running.
+pub(crate) fn evaluate_checks(
+    required: &[String],
+    completed: &[(String, CheckConclusion)],
+) -> ChecksState {
+    let mut all_succeeded = true;
+
+    for name in required {
+        match completed.iter().find(|(n, _)| n == name) {
+            Some((_, CheckConclusion::Success)) => {}
+            Some((
+                _,
+                conclusion @ (CheckConclusion::Failure
+                | CheckConclusion::TimedOut
+                | CheckConclusion::Cancelled),
+            )) => {
+                return ChecksState::Failed {
+                    name: name.clone(),
+                    conclusion: describe_conclusion(conclusion).into(),
+                }
+            }
+            _ => all_succeeded = false,
+        }
+    }
+
+    if all_succeeded {
+        ChecksState::Succeeded
+    } else {
+        ChecksState::Pending
+    }
+}
+
+/// Record the latest state of a commit status context. Each new status for the same context
+/// replaces the previous one.
+pub(crate) async fn record_commit_status(
+    repository: &str,
+    sha: &str,
+    context: &str,
+    state: CommitStatusState,
+) -> Result<(), DbErr> {
+    let row = CommitStatusActiveModel {
+        repository: Set(repository.into()),
+        sha: Set(sha.into()),
+        context: Set(context.into()),
+        state: Set(state),
+        updated_at: Set(Utc::now()),
+    };
+
+    CommitStatusesEntity::insert(row)
+        .on_conflict(
+            OnConflict::columns([
+                CommitStatusesColumn::Repository,
+                CommitStatusesColumn::Sha,
+                CommitStatusesColumn::Context,
+            ])
+            .update_columns([CommitStatusesColumn::State, CommitStatusesColumn::UpdatedAt])
+            .to_owned(),
+        )
+        .exec_without_returning(&get_db().await?)
+        .await?;
+    Ok(())
+}
+
+/// Look up the recorded states for `sha` and combine them for the `required` status contexts.
+pub(crate) async fn get_statuses_state(
+    repository: &str,
+    sha: &str,
+    required: &[String],
+) -> Result<ChecksState, DbErr> {
+    let statuses = CommitStatusesEntity::find()
+        .filter(CommitStatusesColumn::Repository.eq(repository))
+        .filter(CommitStatusesColumn::Sha.eq(sha))
+        .all(&get_db().await?)
+        .await?
+        .into_iter()
+        .map(|s| (s.context, s.state))
+        .collect::<Vec<_>>();
+
+    Ok(evaluate_statuses(required, &statuses))
+}
+
+/// Combine commit status states for the `required` contexts, in the same way as
+/// [`evaluate_checks`].
+pub(crate) fn evaluate_statuses(
+    required: &[String],
+    statuses: &[(String, CommitStatusState)],
+) -> ChecksState {
+    let mut all_succeeded = true;
+
+    for context in required {
+        match statuses.iter().find(|(c, _)| c == context) {
+            Some((_, CommitStatusState::Success)) => {}
+            Some((_, CommitStatusState::Failure)) => {
+                return ChecksState::Failed {
+                    name: context.clone(),
+                    conclusion: "failure".into(),
+                }
+            }
+            Some((_, CommitStatusState::Error)) => {
+                return ChecksState::Failed {
+                    name: context.clone(),
+                    conclusion: "error".into(),
+                }
+            }
+            _ => all_succeeded = false,
+        }
+    }
+
+    if all_succeeded {
+        ChecksState::Succeeded
+    } else {
+        ChecksState::Pending
+    }
+}
+
+/// Forget the recorded check conclusions and commit statuses for `sha`, so that it is tested
+/// from scratch if it is ever tested again.
+pub(crate) async fn forget_checks(repository: &str, sha: &str) -> Result<(), DbErr> {
+    let db = get_db().await?;
+
+    CheckRunsEntity::delete_many()
+        .filter(CheckRunsColumn::Repository.eq(repository))
+        .filter(CheckRunsColumn::HeadSha.eq(sha))
+        .exec(&db)
+        .await?;
+
+    CommitStatusesEntity::delete_many()
+        .filter(CommitStatusesColumn::Repository.eq(repository))
+        .filter(CommitStatusesColumn::Sha.eq(sha))
+        .exec(&db)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{evaluate_checks, evaluate_statuses, ChecksState};
+    use entity::{check_runs::CheckConclusion, commit_statuses::CommitStatusState};
+
+    fn required() -> Vec<String> {
+        vec!["build".into(), "test".into()]
+    }
+
+    #[test]
+    fn all_succeeded() {
+        let completed = vec![
+            ("build".into(), CheckConclusion::Success),
+            ("test".into(), CheckConclusion::Success),
+            ("lint".into(), CheckConclusion::Failure),
+        ];
+        assert_eq!(
+            evaluate_checks(&required(), &completed),
+            ChecksState::Succeeded
+        );
+    }
+
+    #[test]
+    fn pending_until_all_complete() {
+        let completed = vec![("build".into(), CheckConclusion::Success)];
+        assert_eq!(
+            evaluate_checks(&required(), &completed),
+            ChecksState::Pending
+        );
+    }
+
+    #[test]
+    fn failure_wins_over_pending() {
+        let completed = vec![("test".into(), CheckConclusion::TimedOut)];
+        assert_eq!(
+            evaluate_checks(&required(), &completed),
+            ChecksState::Failed {
+                name: "test".into(),
+                conclusion: "timed_out".into()
+            }
+        );
+    }
+
+    #[test]
+    fn no_required_checks() {
+        assert_eq!(evaluate_checks(&[], &[]), ChecksState::Succeeded);
+    }
+
+    #[test]
+    fn status_error_fails() {
+        let statuses = vec![
+            ("build".into(), CommitStatusState::Success),
+            ("test".into(), CommitStatusState::Error),
+        ];
+        assert_eq!(
+            evaluate_statuses(&required(), &statuses),
+            ChecksState::Failed {
+                name: "test".into(),
+                conclusion: "error".into()
+            }
+        );
+    }
+
+    #[test]
+    fn status_pending() {
+        let statuses = vec![
+            ("build".into(), CommitStatusState::Success),
+            ("test".into(), CommitStatusState::Pending),
+        ];
+        assert_eq!(
+            evaluate_statuses(&required(), &statuses),
+            ChecksState::Pending
+        );
+    }
+
+    #[test]
+    fn combined_gates() {
+        assert_eq!(
+            ChecksState::Succeeded.and(ChecksState::Pending),
+            ChecksState::Pending
+        );
+        assert_eq!(
+            ChecksState::Pending.and(ChecksState::Failed {
+                name: "ci".into(),
+                conclusion: "failure".into()
+            }),
+            ChecksState::Failed {
+                name: "ci".into(),
+                conclusion: "failure".into()
+            }
+        );
+        assert_eq!(
+            ChecksState::Succeeded.and(ChecksState::Succeeded),
+            ChecksState::Succeeded
+        );
+    }
+}
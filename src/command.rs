@@ -1,78 +1,705 @@
 // use regex::Regex;
 
-use crate::{config::load_config, logging::info};
+use std::{fmt, iter::Peekable, str::Chars};
+
+use entity::pull_requests::RollupMode;
 
 #[allow(dead_code)]
 #[derive(Debug, PartialEq)]
 pub(crate) enum Command {
     Ping,
+
+    /// Reply with the commands the bot accepts.
+    Help,
+
     Approve,
 
+    /// Approve the pull request on behalf of `reviewer`.
+    ApproveAs {
+        reviewer: String,
+    },
+
+    /// Remove the pull request's approval and take it out of the merge queue.
+    Unapprove,
+
+    /// Allow `user` to approve the pull request. If `user` is `None`, the pull request's author is delegated by default.
+    Delegate {
+        user: Option<String>,
+    },
+
+    /// Revoke a delegation made with `Delegate`.
+    RemoveDelegation,
+
     /// Assign `users` as assignees. If `users` is `None`, the user who issued the command is assigned by default.
     Assign {
         user: Option<String>,
     },
 
     RemoveAssignment,
+
+    /// Set the pull request's priority in the merge queue. Higher priorities are merged first.
+    Priority {
+        priority: i32,
+    },
+
+    /// Mark whether the pull request may be tested in a rollup with other pull requests.
+    Rollup {
+        mode: RollupMode,
+    },
+
+    /// Squash the pull request into a single commit when merging it, or with `squash: false`, use
+    /// the repository's default merge method.
+    Squash {
+        squash: bool,
+    },
+
+    /// Close the repository's tree to pull requests with a priority below `threshold`.
+    TreeClosed {
+        threshold: i32,
+    },
+
+    /// Reopen the repository's tree.
+    TreeOpen,
+
+    /// Put a failed merge back in the merge queue.
+    Retry,
+
+    /// Clear the pull request's merge and try build state.
+    Clean,
+
+    /// Test the merge of the pull request with its base branch on the try branch, without
+    /// merging it.
+    Try,
+}
+
+/// Who may issue a command.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Permission {
+    Anyone,
+    Reviewer,
+
+    /// Reviewers, and the user the pull request has been delegated to.
+    ReviewerOrDelegate,
+}
+
+impl Command {
+    /// The command as it is written in a comment, for use in replies. Also names the command's
+    /// entry in [`COMMANDS`].
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Command::Ping => "hello",
+            Command::Help => "help",
+            Command::Approve => "r+",
+            Command::ApproveAs { .. } => "r=",
+            Command::Unapprove => "r-",
+            Command::Delegate { .. } => "delegate",
+            Command::RemoveDelegation => "delegate-",
+            Command::Assign { user: None } => "claim",
+            Command::Assign { .. } => "assign",
+            Command::RemoveAssignment => "remove-assignment",
+            Command::Priority { .. } => "p=",
+            Command::Rollup { .. } => "rollup",
+            Command::Squash { .. } => "squash",
+            Command::TreeClosed { .. } => "treeclosed=",
+            Command::TreeOpen => "treeclosed-",
+            Command::Retry => "retry",
+            Command::Clean => "clean",
+            Command::Try => "try",
+        }
+    }
+
+    /// Whether the command starts work that finishes after the command has been handled, such
+    /// as a merge or a try build.
+    pub(crate) fn starts_work(&self) -> bool {
+        matches!(
+            self,
+            Command::Approve | Command::ApproveAs { .. } | Command::Retry | Command::Try
+        )
+    }
+
+    pub(crate) fn spec(&self) -> &'static CommandSpec {
+        COMMANDS
+            .iter()
+            .find(|spec| spec.name == self.name())
+            .unwrap()
+    }
+
+    pub(crate) fn permission(&self) -> Permission {
+        self.spec().permission
+    }
 }
 
-fn is_tag_and_not_pattern(input: &str, pattern: &String) -> Option<String> {
-    if input.starts_with("@") && input != pattern {
-        Some(input.replace("@", ""))
-    } else {
-        None
+/// The command as it would be written in a comment, with its arguments.
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::ApproveAs { reviewer } => write!(f, "r={reviewer}"),
+            Command::Delegate { user: None } => write!(f, "delegate+"),
+            Command::Delegate { user: Some(user) } => write!(f, "delegate={user}"),
+            Command::Assign { user: Some(user) } => write!(f, "assign @{user}"),
+            Command::Priority { priority } => write!(f, "p={priority}"),
+            Command::Rollup { mode } => {
+                let mode = match mode {
+                    RollupMode::Never => "never",
+                    RollupMode::Iffy => "iffy",
+                    RollupMode::Maybe => "maybe",
+                    RollupMode::Always => "always",
+                };
+                write!(f, "rollup={mode}")
+            }
+            Command::Squash { squash: false } => write!(f, "squash-"),
+            Command::TreeClosed { threshold } => write!(f, "treeclosed={threshold}"),
+            _ => write!(f, "{}", self.name()),
+        }
+    }
+}
+
+/// A part of a comment addressed to the bot that could not be parsed as a command.
+#[derive(Debug, PartialEq)]
+pub(crate) enum ParseError {
+    /// `word` is not one of the commands in [`COMMANDS`].
+    UnknownCommand { word: String },
+
+    /// `word` starts a known command, but its arguments don't match any of the `usage`s.
+    InvalidArguments {
+        word: String,
+        usage: &'static [&'static str],
+    },
+
+    /// A quoted string starting with `text` is never closed.
+    UnterminatedQuote { text: String },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnknownCommand { word } => write!(f, "`{word}` is not a known command."),
+            ParseError::InvalidArguments { word, usage } => {
+                let usage = usage
+                    .iter()
+                    .map(|u| format!("`{u}`"))
+                    .collect::<Vec<_>>()
+                    .join(" or ");
+                write!(f, "`{word}` is not valid, expected {usage}.")
+            }
+            ParseError::UnterminatedQuote { text } => {
+                write!(f, "The quoted string `{text}` is never closed.")
+            }
+        }
     }
 }
 
-pub(crate) fn parse_command(bot_name: &str, input: &str) -> Vec<Command> {
-    let config = load_config(None).unwrap();
-    let bot_name_pattern = format!("@{bot_name}");
+/// A word of a comment being parsed as a command. A `key=value` word is split at the `=`.
+struct Word<'a> {
+    keyword: &'a str,
+    value: Option<&'a str>,
+}
 
-    let maybe_commands = input
-        .lines()
-        .map(|line| {
-            if line.contains(&bot_name_pattern) && !line.trim_start().starts_with(">") {
-                if let Some(replace_point) = line.find(&bot_name_pattern) {
-                    let replace_range = &line[0..replace_point];
-                    line.replace(replace_range, "")
-                } else {
-                    "".into()
+impl Word<'_> {
+    /// The word as it was written, for use in errors.
+    fn written(&self) -> String {
+        match self.value {
+            Some(value) => format!("{}={value}", self.keyword),
+            None => self.keyword.into(),
+        }
+    }
+}
+
+/// How a command is written, who may use it and what it does. Both [`parse_command`] and the
+/// `help` reply are driven by [`COMMANDS`], so they always agree.
+pub(crate) struct CommandSpec {
+    /// Identifies the entry, as returned by [`Command::name`].
+    pub name: &'static str,
+
+    /// The words that start the command, before any `=value`.
+    keywords: &'static [&'static str],
+
+    /// The ways of writing the command, as shown in `help`.
+    pub usage: &'static [&'static str],
+    pub description: &'static str,
+    pub permission: Permission,
+
+    /// Whether the value may also be given as a separate `@user` word, as in `assign @user`.
+    takes_user: bool,
+    parse: fn(&Word) -> Option<Command>,
+}
+
+/// The user named by a `key=user` value, with or without the `@`.
+fn user_value(word: &Word) -> Option<String> {
+    match word.value {
+        Some(user) if !user.is_empty() => Some(user.trim_start_matches("@").into()),
+        _ => None,
+    }
+}
+
+/// Build a command that takes no arguments.
+fn without_value(word: &Word, command: Command) -> Option<Command> {
+    match word.value {
+        None => Some(command),
+        Some(_) => None,
+    }
+}
+
+pub(crate) const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "hello",
+        keywords: &["hello"],
+        usage: &["hello"],
+        description: "Check that the bot is alive.",
+        permission: Permission::Anyone,
+        takes_user: false,
+        parse: |w| without_value(w, Command::Ping),
+    },
+    CommandSpec {
+        name: "help",
+        keywords: &["help"],
+        usage: &["help"],
+        description: "List the commands the bot accepts.",
+        permission: Permission::Anyone,
+        takes_user: false,
+        parse: |w| without_value(w, Command::Help),
+    },
+    CommandSpec {
+        name: "r+",
+        keywords: &["r+"],
+        usage: &["r+"],
+        description: "Approve the pull request and add it to the merge queue.",
+        permission: Permission::ReviewerOrDelegate,
+        takes_user: false,
+        parse: |w| without_value(w, Command::Approve),
+    },
+    CommandSpec {
+        name: "r=",
+        keywords: &["r"],
+        usage: &["r=<user>"],
+        description: "Approve the pull request on behalf of another reviewer.",
+        permission: Permission::Reviewer,
+        takes_user: false,
+        parse: |w| user_value(w).map(|reviewer| Command::ApproveAs { reviewer }),
+    },
+    CommandSpec {
+        name: "r-",
+        keywords: &["r-"],
+        usage: &["r-"],
+        description: "Remove the approval and take the pull request out of the merge queue.",
+        permission: Permission::ReviewerOrDelegate,
+        takes_user: false,
+        parse: |w| without_value(w, Command::Unapprove),
+    },
+    CommandSpec {
+        name: "delegate",
+        keywords: &["delegate+", "delegate"],
+        usage: &["delegate+", "delegate=<user>"],
+        description: "Allow the author, or the given user, to approve the pull request.",
+        permission: Permission::Reviewer,
+        takes_user: false,
+        parse: |w| match w.keyword {
+            "delegate+" => without_value(w, Command::Delegate { user: None }),
+            _ => user_value(w).map(|user| Command::Delegate { user: Some(user) }),
+        },
+    },
+    CommandSpec {
+        name: "delegate-",
+        keywords: &["delegate-"],
+        usage: &["delegate-"],
+        description: "Revoke the delegation.",
+        permission: Permission::Reviewer,
+        takes_user: false,
+        parse: |w| without_value(w, Command::RemoveDelegation),
+    },
+    CommandSpec {
+        name: "claim",
+        keywords: &["claim", "c"],
+        usage: &["claim", "c"],
+        description: "Assign yourself to the issue.",
+        permission: Permission::Anyone,
+        takes_user: false,
+        parse: |w| without_value(w, Command::Assign { user: None }),
+    },
+    CommandSpec {
+        name: "assign",
+        keywords: &["assign", "a"],
+        usage: &["assign @user", "a @user"],
+        description: "Assign the user to the issue, or yourself if no user is given.",
+        permission: Permission::Anyone,
+        takes_user: true,
+        parse: |w| match w.value {
+            None => Some(Command::Assign { user: None }),
+            Some(_) => user_value(w).map(|user| Command::Assign { user: Some(user) }),
+        },
+    },
+    CommandSpec {
+        name: "remove-assignment",
+        keywords: &["remove-assignment", "ra"],
+        usage: &["remove-assignment", "ra"],
+        description: "Unassign yourself from the issue.",
+        permission: Permission::Anyone,
+        takes_user: false,
+        parse: |w| without_value(w, Command::RemoveAssignment),
+    },
+    CommandSpec {
+        name: "p=",
+        keywords: &["p", "priority"],
+        usage: &["p=<N>", "priority=<N>"],
+        description: "Set the priority in the merge queue. Higher priorities are merged first.",
+        permission: Permission::Reviewer,
+        takes_user: false,
+        parse: |w| {
+            w.value
+                .and_then(|v| v.parse().ok())
+                .map(|priority| Command::Priority { priority })
+        },
+    },
+    CommandSpec {
+        name: "rollup",
+        keywords: &["rollup", "rollup-"],
+        usage: &["rollup", "rollup=<never|iffy|maybe|always>", "rollup-"],
+        description: "Mark whether the pull request may be tested in a rollup with others.",
+        permission: Permission::Reviewer,
+        takes_user: false,
+        parse: |w| {
+            let mode = match (w.keyword, w.value) {
+                ("rollup", None) | ("rollup", Some("always")) => RollupMode::Always,
+                ("rollup-", None) | ("rollup", Some("maybe")) => RollupMode::Maybe,
+                ("rollup", Some("iffy")) => RollupMode::Iffy,
+                ("rollup", Some("never")) => RollupMode::Never,
+                _ => return None,
+            };
+            Some(Command::Rollup { mode })
+        },
+    },
+    CommandSpec {
+        name: "squash",
+        keywords: &["squash", "squash-"],
+        usage: &["squash", "squash-"],
+        description: "Squash the pull request into one commit, or use the default merge method.",
+        permission: Permission::Reviewer,
+        takes_user: false,
+        parse: |w| {
+            without_value(
+                w,
+                Command::Squash {
+                    squash: w.keyword == "squash",
+                },
+            )
+        },
+    },
+    CommandSpec {
+        name: "treeclosed=",
+        keywords: &["treeclosed"],
+        usage: &["treeclosed=<N>"],
+        description: "Close the tree to pull requests with a priority below N.",
+        permission: Permission::Reviewer,
+        takes_user: false,
+        parse: |w| {
+            w.value
+                .and_then(|v| v.parse().ok())
+                .map(|threshold| Command::TreeClosed { threshold })
+        },
+    },
+    CommandSpec {
+        name: "treeclosed-",
+        keywords: &["treeclosed-"],
+        usage: &["treeclosed-"],
+        description: "Reopen the tree.",
+        permission: Permission::Reviewer,
+        takes_user: false,
+        parse: |w| without_value(w, Command::TreeOpen),
+    },
+    CommandSpec {
+        name: "retry",
+        keywords: &["retry"],
+        usage: &["retry"],
+        description: "Put a failed merge back in the merge queue.",
+        permission: Permission::Reviewer,
+        takes_user: false,
+        parse: |w| without_value(w, Command::Retry),
+    },
+    CommandSpec {
+        name: "clean",
+        keywords: &["clean"],
+        usage: &["clean"],
+        description: "Clear the merge and try build state of the pull request.",
+        permission: Permission::Reviewer,
+        takes_user: false,
+        parse: |w| without_value(w, Command::Clean),
+    },
+    CommandSpec {
+        name: "try",
+        keywords: &["try"],
+        usage: &["try"],
+        description: "Test the merge with the base branch without merging it.",
+        permission: Permission::Reviewer,
+        takes_user: false,
+        parse: |w| without_value(w, Command::Try),
+    },
+];
+
+/// Build the reply to `help`: a table of every command, marking the ones the commenter is
+/// allowed to use.
+pub(crate) fn help_text(bot_name: &str, allowed: impl Fn(Permission) -> bool) -> String {
+    let mut text = format!(
+        "Commands are written after a mention of @{bot_name}, e.g. `@{bot_name} r+`.\n\n| Command | Description | Allowed |\n| --- | --- | --- |\n"
+    );
+
+    for spec in COMMANDS {
+        let usage = spec
+            .usage
+            .iter()
+            .map(|u| format!("`{u}`"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let allowed = if allowed(spec.permission) {
+            ":white_check_mark:"
+        } else {
+            ":no_entry_sign:"
+        };
+        text.push_str(&format!("| {usage} | {} | {allowed} |\n", spec.description));
+    }
+
+    text
+}
+
+/// A token of the text following a mention of the bot.
+#[derive(Debug, PartialEq)]
+enum Token {
+    /// A word, split at its first `=` into a keyword and a value. Either may be quoted.
+    Word {
+        keyword: String,
+        value: Option<String>,
+    },
+
+    /// A `,` between two commands.
+    Comma,
+}
+
+/// Splits text into [`Token`]s. Tokens are read one at a time, so that text after the commands
+/// is never looked at.
+struct Lexer<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(text: &'a str) -> Self {
+        Lexer {
+            chars: text.chars().peekable(),
+        }
+    }
+
+    fn word(&mut self) -> Result<Token, ParseError> {
+        let keyword = self.text(true)?;
+        let value = match self.chars.next_if_eq(&'=') {
+            Some(_) => Some(self.text(false)?),
+            None => None,
+        };
+        Ok(Token::Word { keyword, value })
+    }
+
+    /// Read a quoted string, or the text up to the next whitespace or `,`. A keyword also ends
+    /// at `=`.
+    fn text(&mut self, keyword: bool) -> Result<String, ParseError> {
+        let mut text = String::new();
+
+        if let Some(quote) = self.chars.next_if(|c| *c == '"' || *c == '\'') {
+            loop {
+                match self.chars.next() {
+                    Some(c) if c == quote => return Ok(text),
+                    Some(c) => text.push(c),
+                    None => {
+                        return Err(ParseError::UnterminatedQuote {
+                            text: format!("{quote}{text}"),
+                        })
+                    }
                 }
+            }
+        }
+
+        while let Some(c) = self
+            .chars
+            .next_if(|c| !c.is_whitespace() && *c != ',' && !(keyword && *c == '='))
+        {
+            text.push(c);
+        }
+        Ok(text)
+    }
+}
+
+impl Iterator for Lexer<'_> {
+    type Item = Result<Token, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+        match self.chars.peek()? {
+            ',' => {
+                self.chars.next();
+                Some(Ok(Token::Comma))
+            }
+            _ => Some(self.word()),
+        }
+    }
+}
+
+/// Remove fenced code blocks and quoted lines, and blank out inline code, so that commands shown
+/// as examples are not run.
+fn strip_code(input: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut in_fence = false;
+
+    for line in input.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence || trimmed.starts_with('>') {
+            continue;
+        }
+
+        // Pieces alternate between text and code. An unmatched backtick is kept as text.
+        let pieces = line.split('`').collect::<Vec<_>>();
+        let unmatched = pieces.len() % 2 == 0;
+        let mut stripped = String::new();
+        for (i, piece) in pieces.iter().enumerate() {
+            if i % 2 == 0 {
+                stripped.push_str(piece);
+            } else if unmatched && i == pieces.len() - 1 {
+                stripped.push('`');
+                stripped.push_str(piece);
             } else {
-                "".into()
+                stripped.push(' ');
             }
+        }
+        lines.push(stripped);
+    }
+
+    lines
+}
+
+/// Find each mention of the bot in `line`, returning the text up to the next mention and
+/// whether the mention starts the line.
+fn mentions<'a>(line: &'a str, mention: &str) -> Vec<(bool, &'a str)> {
+    let starts = line
+        .match_indices(mention)
+        .map(|(start, _)| start)
+        .filter(|start| {
+            // `@bot` must not be the start of a longer name, such as `@bot-staging`
+            !line[start + mention.len()..]
+                .starts_with(|c: char| c.is_alphanumeric() || c == '-' || c == '_')
         })
         .collect::<Vec<_>>();
 
-    let mut commands = Vec::new();
-    for maybe_command in maybe_commands.iter() {
-        let pieces = maybe_command.split_whitespace();
-        for (i, word) in pieces.clone().enumerate() {
-            if word == &bot_name_pattern || word == &format!("{bot_name_pattern}:") {
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, start)| {
+            let end = starts.get(i + 1).copied().unwrap_or(line.len());
+            let text = &line[start + mention.len()..end];
+            (
+                line[..*start].trim().is_empty(),
+                text.strip_prefix(':').unwrap_or(text),
+            )
+        })
+        .collect()
+}
+
+/// Parse the text following a mention of the bot.
+///
+/// The text is a list of commands separated by whitespace or `,`. The first word that is not a
+/// command ends the list, so that a mention can be followed by prose, e.g. `@bot r+ thanks!`.
+/// Where a command is clearly expected, at the start of a line addressed to the bot or after a
+/// `,`, an unknown word or an unterminated quote is an error instead.
+fn parse_mention(text: &str, addressed: bool, results: &mut Vec<Result<Command, ParseError>>) {
+    let mut tokens = Lexer::new(text).peekable();
+    let mut expect_command = addressed;
+
+    while let Some(token) = tokens.next() {
+        let (keyword, mut value) = match token {
+            Ok(Token::Comma) => {
+                expect_command = true;
                 continue;
             }
+            Ok(Token::Word { keyword, value }) => (keyword, value),
+            Err(e) => {
+                if expect_command {
+                    results.push(Err(e));
+                }
+                return;
+            }
+        };
 
-            match word {
-                "hello" => commands.push(Command::Ping),
-                "r+" => commands.push(Command::Approve),
-                "c" | "claim" => commands.push(Command::Assign { user: None }),
-                "a" | "assign" => commands.push(Command::Assign {
-                    user: (pieces.clone())
-                        .skip(i + 1)
-                        .next()
-                        .map_or(None, |name| is_tag_and_not_pattern(name, &bot_name_pattern)),
-                }),
-                "ra" | "remove-assignment" => commands.push(Command::RemoveAssignment),
-                _ => info(format!("Unknown command: {word}"), Some(&config)),
+        let spec = match COMMANDS
+            .iter()
+            .find(|s| s.keywords.contains(&keyword.as_str()))
+        {
+            Some(spec) => spec,
+            None => {
+                if expect_command {
+                    results.push(Err(ParseError::UnknownCommand { word: keyword }));
+                }
+                return;
+            }
+        };
+
+        if spec.takes_user && value.is_none() {
+            let user = tokens.next_if(|t| {
+                matches!(t, Ok(Token::Word { keyword, value: None }) if keyword.starts_with('@'))
+            });
+            if let Some(Ok(Token::Word { keyword, .. })) = user {
+                value = Some(keyword);
             }
         }
+
+        let word = Word {
+            keyword: &keyword,
+            value: value.as_deref(),
+        };
+        results.push(
+            (spec.parse)(&word).ok_or_else(|| ParseError::InvalidArguments {
+                word: word.written(),
+                usage: spec.usage,
+            }),
+        );
+        expect_command = false;
     }
-    commands
+}
+
+/// Parse the commands addressed to the bot in a comment, in the order they are written.
+pub(crate) fn parse_command(bot_name: &str, input: &str) -> Vec<Result<Command, ParseError>> {
+    let bot_mention = format!("@{bot_name}");
+
+    let mut results = Vec::new();
+    for line in strip_code(input) {
+        for (addressed, text) in mentions(&line, &bot_mention) {
+            parse_mention(text, addressed, &mut results);
+        }
+    }
+    results
+}
+
+/// Parse the commands added by editing a comment from `before` to `after`. The commands already
+/// in `before` were handled when the comment was written, so they are left out, as are errors
+/// that were already reported. Like a repeat of a command within a comment, a command already in
+/// `before` that is written again is not run again.
+pub(crate) fn parse_edited_command(
+    bot_name: &str,
+    before: &str,
+    after: &str,
+) -> Vec<Result<Command, ParseError>> {
+    let previous = parse_command(bot_name, before);
+
+    parse_command(bot_name, after)
+        .into_iter()
+        .filter(|result| !previous.contains(result))
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::command::{parse_command, Command};
+    use crate::command::{
+        help_text, parse_command, parse_edited_command, Command, ParseError, Permission, COMMANDS,
+    };
+    use entity::pull_requests::RollupMode;
 
     #[test]
     fn command_parses() {
@@ -84,7 +711,10 @@ mod tests {
     }
 
     fn assert_command(bot_name: &str, input: &str, assertion: Command) {
-        assert_eq!(parse_command(bot_name, input).get(0).unwrap(), &assertion)
+        assert_eq!(
+            parse_command(bot_name, input).get(0).unwrap(),
+            &Ok(assertion)
+        )
     }
 
     #[test]
@@ -117,4 +747,304 @@ mod tests {
     fn remove_assignment() {
         assert_command("bot", "@bot remove-assignment", Command::RemoveAssignment);
     }
+
+    #[test]
+    fn priority() {
+        assert_command("bot", "@bot p=10", Command::Priority { priority: 10 });
+        assert_command(
+            "bot",
+            "@bot priority=-1",
+            Command::Priority { priority: -1 },
+        );
+    }
+
+    #[test]
+    fn approve_with_priority() {
+        assert_eq!(
+            parse_command("bot", "@bot r+ p=10"),
+            vec![Ok(Command::Approve), Ok(Command::Priority { priority: 10 })]
+        );
+    }
+
+    #[test]
+    fn invalid_priority() {
+        assert_eq!(
+            parse_command("bot", "@bot p=high"),
+            vec![Err(ParseError::InvalidArguments {
+                word: "p=high".into(),
+                usage: &["p=<N>", "priority=<N>"],
+            })]
+        );
+    }
+
+    #[test]
+    fn rollup() {
+        assert_command(
+            "bot",
+            "@bot rollup",
+            Command::Rollup {
+                mode: RollupMode::Always,
+            },
+        );
+        assert_command(
+            "bot",
+            "@bot rollup=never",
+            Command::Rollup {
+                mode: RollupMode::Never,
+            },
+        );
+        assert_command(
+            "bot",
+            "@bot rollup-",
+            Command::Rollup {
+                mode: RollupMode::Maybe,
+            },
+        );
+    }
+
+    #[test]
+    fn approve_with_rollup() {
+        assert_eq!(
+            parse_command("bot", "@bot r+ rollup=iffy"),
+            vec![
+                Ok(Command::Approve),
+                Ok(Command::Rollup {
+                    mode: RollupMode::Iffy
+                })
+            ]
+        );
+    }
+
+    #[test]
+    fn try_build() {
+        assert_command("bot", "@bot try", Command::Try);
+    }
+
+    #[test]
+    fn unapprove() {
+        assert_command("bot", "@bot r-", Command::Unapprove);
+    }
+
+    #[test]
+    fn approve_as() {
+        assert_command(
+            "bot",
+            "@bot r=alice",
+            Command::ApproveAs {
+                reviewer: "alice".into(),
+            },
+        );
+        assert_command(
+            "bot",
+            "@bot r=@alice",
+            Command::ApproveAs {
+                reviewer: "alice".into(),
+            },
+        );
+        assert_eq!(
+            parse_command("bot", "@bot r="),
+            vec![Err(ParseError::InvalidArguments {
+                word: "r=".into(),
+                usage: &["r=<user>"],
+            })]
+        );
+    }
+
+    #[test]
+    fn delegate() {
+        assert_command("bot", "@bot delegate+", Command::Delegate { user: None });
+        assert_command(
+            "bot",
+            "@bot delegate=@alice",
+            Command::Delegate {
+                user: Some("alice".into()),
+            },
+        );
+        assert_command("bot", "@bot delegate-", Command::RemoveDelegation);
+    }
+
+    #[test]
+    fn squash() {
+        assert_command("bot", "@bot squash", Command::Squash { squash: true });
+        assert_command("bot", "@bot squash-", Command::Squash { squash: false });
+    }
+
+    #[test]
+    fn tree_closed() {
+        assert_command(
+            "bot",
+            "@bot treeclosed=100",
+            Command::TreeClosed { threshold: 100 },
+        );
+        assert_command("bot", "@bot treeclosed-", Command::TreeOpen);
+    }
+
+    #[test]
+    fn retry_and_clean() {
+        assert_command("bot", "@bot retry", Command::Retry);
+        assert_command("bot", "@bot clean", Command::Clean);
+    }
+
+    #[test]
+    fn help() {
+        assert_command("bot", "@bot help", Command::Help);
+    }
+
+    #[test]
+    fn every_command_has_a_spec() {
+        let commands = parse_command(
+            "bot",
+            "@bot hello help r+ r=alice r- delegate+ delegate- claim assign @alice ra p=1 rollup squash treeclosed=1 treeclosed- retry clean try",
+        );
+        assert_eq!(commands.len(), COMMANDS.len());
+        for command in commands {
+            let command = command.unwrap();
+            assert_eq!(command.spec().name, command.name());
+        }
+    }
+
+    #[test]
+    fn help_lists_every_command() {
+        let text = help_text("bot", |p| p == Permission::Anyone);
+        for spec in COMMANDS {
+            assert!(text.contains(&format!("`{}`", spec.usage[0])));
+        }
+        assert!(text.contains("| `hello` | Check that the bot is alive. | :white_check_mark: |"));
+        assert!(text.contains(
+            "| `r+` | Approve the pull request and add it to the merge queue. | :no_entry_sign: |"
+        ));
+    }
+
+    #[test]
+    fn comma_separated() {
+        assert_eq!(
+            parse_command("bot", "@bot r+, p=1,rollup"),
+            vec![
+                Ok(Command::Approve),
+                Ok(Command::Priority { priority: 1 }),
+                Ok(Command::Rollup {
+                    mode: RollupMode::Always
+                })
+            ]
+        );
+    }
+
+    #[test]
+    fn quoted_values() {
+        assert_command(
+            "bot",
+            r#"@bot r="alice""#,
+            Command::ApproveAs {
+                reviewer: "alice".into(),
+            },
+        );
+        assert_command(
+            "bot",
+            "@bot delegate='@alice'",
+            Command::Delegate {
+                user: Some("alice".into()),
+            },
+        );
+        assert_eq!(
+            parse_command("bot", r#"@bot r="alice"#),
+            vec![Err(ParseError::UnterminatedQuote {
+                text: r#""alice"#.into()
+            })]
+        );
+    }
+
+    #[test]
+    fn prose_ends_commands() {
+        assert_eq!(
+            parse_command("bot", "@bot r+ looks good, p=1"),
+            vec![Ok(Command::Approve)]
+        );
+        assert_eq!(parse_command("bot", "thanks @bot for the review"), vec![]);
+        assert_eq!(
+            parse_command("bot", "@bot r+ 'cause CI is green"),
+            vec![Ok(Command::Approve)]
+        );
+        assert_eq!(
+            parse_command("bot", "that may have a command. @bot: r+"),
+            vec![Ok(Command::Approve)]
+        );
+    }
+
+    #[test]
+    fn unknown_command_when_addressed() {
+        assert_eq!(
+            parse_command("bot", "@bot please r+ this"),
+            vec![Err(ParseError::UnknownCommand {
+                word: "please".into()
+            })]
+        );
+        assert_eq!(
+            parse_command("bot", "@bot r+, rolup"),
+            vec![
+                Ok(Command::Approve),
+                Err(ParseError::UnknownCommand {
+                    word: "rolup".into()
+                })
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_code_and_quotes() {
+        let comment = "Use `@bot r+` to approve.\n```\n@bot r+\n```\n> @bot r+\n@bot try";
+        assert_eq!(parse_command("bot", comment), vec![Ok(Command::Try)]);
+    }
+
+    #[test]
+    fn ignores_other_mentions() {
+        assert_eq!(parse_command("bot", "@bot-staging r+"), vec![]);
+        assert_eq!(
+            parse_command("bot", "@bot r+ @bot p=2"),
+            vec![Ok(Command::Approve), Ok(Command::Priority { priority: 2 })]
+        );
+    }
+
+    #[test]
+    fn parse_error_messages() {
+        assert_eq!(
+            ParseError::InvalidArguments {
+                word: "p=high".into(),
+                usage: &["p=<N>", "priority=<N>"],
+            }
+            .to_string(),
+            "`p=high` is not valid, expected `p=<N>` or `priority=<N>`."
+        );
+    }
+
+    #[test]
+    fn edit_adds_commands() {
+        assert_eq!(
+            parse_edited_command("bot", "@bot r+", "@bot r+ p=1"),
+            vec![Ok(Command::Priority { priority: 1 })]
+        );
+        assert_eq!(
+            parse_edited_command("bot", "@bot r+", "@bot r+\n@bot r+"),
+            vec![]
+        );
+        assert_eq!(
+            parse_edited_command("bot", "@bot r+ rolup", "@bot r+ try"),
+            vec![Ok(Command::Try)]
+        );
+        assert_eq!(
+            parse_edited_command("bot", "@bot try", "typo fixed. @bot try"),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn display_parses_back() {
+        let comment = "@bot hello help r+ r=alice r- delegate+ delegate=bob delegate- claim assign @carol ra p=-1 rollup=iffy squash squash- treeclosed=5 treeclosed- retry clean try";
+        for command in parse_command("bot", comment) {
+            let command = command.unwrap();
+            assert_eq!(
+                parse_command("bot", &format!("@bot {command}")),
+                vec![Ok(command)]
+            );
+        }
+    }
 }
@@ -8,18 +8,22 @@ use std::{
     sync::Arc,
 };
 
-use crate::CONFIG;
+use crate::{github::model::Reaction, CONFIG};
 
 pub(crate) mod github;
 pub(crate) mod logging;
+pub(crate) mod permissions;
 pub(crate) mod repo;
 pub(crate) mod ssl;
 pub(crate) mod tests_config;
+pub(crate) mod webhooks;
 
 use github::*;
 use logging::*;
+use permissions::*;
 use repo::*;
 use ssl::*;
+use webhooks::*;
 
 const DEFAULT_CONFIG_FILE_NAME: &str = "yad.toml";
 
@@ -31,6 +35,8 @@ pub(crate) struct Config {
     logging: Option<LoggingConfig>,
     database: Option<DatabaseConfig>,
     pub actions: Option<ActionsConfig>,
+    webhooks: Option<WebhooksConfig>,
+    permissions: Option<PermissionsConfig>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -48,6 +54,12 @@ pub(crate) struct ServerConfig {
 const DEFAULT_HOST: &str = "127.0.0.1";
 const DEFAULT_PORT: u16 = 8000;
 const DEFAULT_DATABASE_PATH: &str = "yad.db";
+const DEFAULT_DELIVERY_RETENTION_HOURS: i64 = 72;
+const DEFAULT_STORED_DELIVERY_LIMIT: u64 = 1000;
+const DEFAULT_PERMISSION_CACHE_SECONDS: u64 = 300;
+const DEFAULT_ACCEPTED_REACTION: Reaction = Reaction::ThumbsUp;
+const DEFAULT_REJECTED_REACTION: Reaction = Reaction::Confused;
+const DEFAULT_STARTED_REACTION: Reaction = Reaction::Eyes;
 
 impl ServerConfig {
     pub(crate) fn get_addr(&self) -> SocketAddrV4 {
@@ -103,6 +115,74 @@ impl Config {
             None => DEFAULT_DATABASE_PATH.into(),
         }
     }
+
+    pub(crate) fn delivery_retention_hours(&self) -> i64 {
+        match &self.webhooks {
+            Some(w) => match w.delivery_retention_hours {
+                Some(h) => h,
+                None => DEFAULT_DELIVERY_RETENTION_HOURS,
+            },
+            None => DEFAULT_DELIVERY_RETENTION_HOURS,
+        }
+    }
+
+    pub(crate) fn stored_delivery_limit(&self) -> u64 {
+        match &self.webhooks {
+            Some(w) => match w.stored_delivery_limit {
+                Some(l) => l,
+                None => DEFAULT_STORED_DELIVERY_LIMIT,
+            },
+            None => DEFAULT_STORED_DELIVERY_LIMIT,
+        }
+    }
+
+    pub(crate) fn permission_cache_seconds(&self) -> u64 {
+        match &self.permissions {
+            Some(p) => match p.cache_seconds {
+                Some(s) => s,
+                None => DEFAULT_PERMISSION_CACHE_SECONDS,
+            },
+            None => DEFAULT_PERMISSION_CACHE_SECONDS,
+        }
+    }
+
+    fn reactions(&self) -> Option<&ReactionsConfig> {
+        self.actions.as_ref().and_then(|a| a.reactions.as_ref())
+    }
+
+    /// The reaction added to a comment whose commands were carried out.
+    pub(crate) fn accepted_reaction(&self) -> Reaction {
+        match self.reactions().and_then(|r| r.accepted) {
+            Some(r) => r,
+            None => DEFAULT_ACCEPTED_REACTION,
+        }
+    }
+
+    /// The reaction added to a comment with a command that could not be parsed, was refused or
+    /// failed.
+    pub(crate) fn rejected_reaction(&self) -> Reaction {
+        match self.reactions().and_then(|r| r.rejected) {
+            Some(r) => r,
+            None => DEFAULT_REJECTED_REACTION,
+        }
+    }
+
+    /// The reaction added to a comment when the bot starts working on its commands.
+    pub(crate) fn started_reaction(&self) -> Reaction {
+        match self.reactions().and_then(|r| r.started) {
+            Some(r) => r,
+            None => DEFAULT_STARTED_REACTION,
+        }
+    }
+
+    /// Find the configuration for the repository `owner/name`. Repositories are keyed
+    /// by name in the configuration file, with the owner given inside the table.
+    pub(crate) fn repo(&self, owner: &str, name: &str) -> Option<&RepoConfig> {
+        self.repos
+            .iter()
+            .find(|(repo_name, repo)| repo_name.as_str() == name && repo.owner() == owner)
+            .map(|(_, repo)| repo)
+    }
 }
 
 pub(crate) fn load_config(config_file: Option<&str>) -> Result<Config, Box<dyn Error>> {
@@ -125,9 +205,19 @@ pub(crate) fn get_config() -> Arc<Config> {
 #[derive(Debug, Deserialize)]
 pub(crate) struct ActionsConfig {
     pub ping: Option<PingConfig>,
+    pub reactions: Option<ReactionsConfig>,
 }
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct PingConfig {
     pub message: Option<String>,
 }
+
+/// The reactions used to acknowledge commands, named as in Github's Reactions API, e.g. `+1`,
+/// `confused` or `eyes`.
+#[derive(Debug, Deserialize)]
+pub(crate) struct ReactionsConfig {
+    pub accepted: Option<Reaction>,
+    pub rejected: Option<Reaction>,
+    pub started: Option<Reaction>,
+}
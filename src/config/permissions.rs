@@ -0,0 +1,8 @@
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct PermissionsConfig {
+    /// How long, in seconds, a user's looked up review rights are cached. If this value is
+    /// `None`, the default of 300 seconds is used.
+    pub cache_seconds: Option<u64>,
+}
@@ -2,6 +2,23 @@ use serde::Deserialize;
 
 use super::tests_config::TestsConfig;
 
+pub(crate) const DEFAULT_AUTO_BRANCH: &str = "auto";
+pub(crate) const DEFAULT_TRY_BRANCH: &str = "try";
+
+/// How the commits of a pull request end up on the base branch.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum MergeMethod {
+    /// A merge commit with the pull request's head as its second parent.
+    Merge,
+
+    /// A single commit with the changes of the whole pull request.
+    Squash,
+
+    /// Each commit of the pull request replayed on top of the base branch.
+    Rebase,
+}
+
 #[derive(Debug, Deserialize)]
 pub(crate) struct RepoConfig {
     owner: String,
@@ -10,4 +27,110 @@ pub(crate) struct RepoConfig {
 
     /// Check names on the Github workflow runs to wait for before merging
     checks: Option<Vec<String>>,
+
+    /// Commit status contexts to wait for before merging, for CI that reports through the
+    /// legacy Status API rather than checks
+    statuses: Option<Vec<String>>,
+
+    /// The branch used to test the merge of a pull request before the base branch is moved to it.
+    /// Defaults to `auto`.
+    auto_branch: Option<String>,
+
+    /// The branch used for `try` builds, which test the merge of a pull request without merging
+    /// it. Defaults to `try`.
+    try_branch: Option<String>,
+
+    /// Treat an approving pull request review from an authorized reviewer as `r+`. Defaults to
+    /// `false`.
+    review_approvals: Option<bool>,
+
+    /// How pull requests are merged unless they are marked with `squash`. Defaults to `merge`.
+    merge_method: Option<MergeMethod>,
+
+    /// Users allowed to issue privileged commands such as `r+`. If neither this nor
+    /// `reviewer_teams` is set, anyone with write access to the repository is a reviewer.
+    reviewers: Option<Vec<String>>,
+
+    /// Slugs of teams in the repository owner's organization whose members are reviewers.
+    reviewer_teams: Option<Vec<String>>,
+
+    /// Stop starting merges for the repository, for example while the base branch is broken.
+    /// Defaults to `false`. The `treeclosed=N` and `treeclosed-` commands override this.
+    tree_closed: Option<bool>,
+
+    /// While the tree is closed, pull requests with at least this priority are still merged.
+    /// If unset, nothing is merged while the tree is closed.
+    tree_closed_priority: Option<i32>,
+}
+
+impl RepoConfig {
+    pub(crate) fn owner(&self) -> &str {
+        &self.owner
+    }
+
+    /// The shared secret configured on the repository's webhook, used to verify
+    /// the `X-Hub-Signature-256` header of each delivery.
+    pub(crate) fn secret(&self) -> &str {
+        &self.secret
+    }
+
+    pub(crate) fn checks(&self) -> &[String] {
+        match &self.checks {
+            Some(c) => c,
+            None => &[],
+        }
+    }
+
+    pub(crate) fn statuses(&self) -> &[String] {
+        match &self.statuses {
+            Some(s) => s,
+            None => &[],
+        }
+    }
+
+    pub(crate) fn auto_branch(&self) -> &str {
+        match &self.auto_branch {
+            Some(b) => b,
+            None => DEFAULT_AUTO_BRANCH,
+        }
+    }
+
+    pub(crate) fn try_branch(&self) -> &str {
+        match &self.try_branch {
+            Some(b) => b,
+            None => DEFAULT_TRY_BRANCH,
+        }
+    }
+
+    pub(crate) fn review_approvals(&self) -> bool {
+        self.review_approvals.unwrap_or(false)
+    }
+
+    pub(crate) fn merge_method(&self) -> MergeMethod {
+        self.merge_method.unwrap_or(MergeMethod::Merge)
+    }
+
+    pub(crate) fn reviewers(&self) -> &[String] {
+        match &self.reviewers {
+            Some(r) => r,
+            None => &[],
+        }
+    }
+
+    pub(crate) fn reviewer_teams(&self) -> &[String] {
+        match &self.reviewer_teams {
+            Some(t) => t,
+            None => &[],
+        }
+    }
+
+    /// The lowest priority a pull request must have to be merged, or `None` if the tree is open
+    /// and every pull request can be merged.
+    pub(crate) fn merge_threshold(&self) -> Option<i32> {
+        if self.tree_closed.unwrap_or(false) {
+            Some(self.tree_closed_priority.unwrap_or(i32::MAX))
+        } else {
+            None
+        }
+    }
 }
@@ -0,0 +1,12 @@
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct WebhooksConfig {
+    /// How long, in hours, a delivery id is remembered for deduplication. If this value is
+    /// `None`, the default of 72 hours is used.
+    pub delivery_retention_hours: Option<i64>,
+
+    /// The number of most recent raw deliveries to keep in `webhook_deliveries`. If this value
+    /// is `None`, the default of 1000 is used.
+    pub stored_delivery_limit: Option<u64>,
+}
@@ -0,0 +1,203 @@
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use chrono::{Duration, Utc};
+use entity::{
+    processed_deliveries::{
+        ActiveModel as ProcessedDeliveryActiveModel, Column as ProcessedDeliveriesColumn,
+        Entity as ProcessedDeliveriesEntity,
+    },
+    webhook_deliveries::{
+        ActiveModel as WebhookDeliveryActiveModel, Column as WebhookDeliveriesColumn,
+        Entity as WebhookDeliveriesEntity, WebhookDeliveryOutcome,
+    },
+};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DbErr, EntityTrait, QueryFilter, QueryOrder, QuerySelect, Set,
+};
+use std::{collections::BTreeMap, error::Error};
+
+use crate::{
+    config::get_config,
+    db::get_db,
+    github::model::User,
+    logging::{error, info},
+    routes::{
+        dispatch, parse_payload, verify_delivery, WebhookResponse, GITHUB_DELIVERY_KEY,
+        GITHUB_EVENT_KEY,
+    },
+};
+
+/// Record that the delivery `delivery_id` is being processed.
+///
+/// Returns `false` if the delivery has already been recorded, in which case it must not be
+/// dispatched again. The check and the insert are a single statement so that two concurrent
+/// deliveries with the same id cannot both be processed.
+pub(crate) async fn claim_delivery(delivery_id: &str) -> Result<bool, DbErr> {
+    let db = get_db().await?;
+
+    let row = ProcessedDeliveryActiveModel {
+        delivery_id: Set(delivery_id.into()),
+        received_at: Set(Utc::now()),
+    };
+
+    let inserted = ProcessedDeliveriesEntity::insert(row)
+        .on_conflict(
+            sea_orm::sea_query::OnConflict::column(ProcessedDeliveriesColumn::DeliveryId)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(&db)
+        .await?;
+
+    Ok(inserted == 1)
+}
+
+/// Forget the delivery `delivery_id` so that a redelivery from Github is processed again.
+/// Used when handling the delivery failed.
+pub(crate) async fn release_delivery(delivery_id: &str) -> Result<(), DbErr> {
+    ProcessedDeliveriesEntity::delete_by_id(delivery_id.to_string())
+        .exec(&get_db().await?)
+        .await?;
+    Ok(())
+}
+
+/// Periodically expire old delivery ids, off the path of handling deliveries.
+pub(crate) async fn expiry_server() {
+    const SLEEP_LENGTH: std::time::Duration = std::time::Duration::from_secs(600);
+
+    loop {
+        if let Err(e) = expire_deliveries().await {
+            error(
+                format!("Failed to expire old delivery ids. {e}"),
+                Some(&get_config()),
+            );
+        }
+        tokio::time::sleep(SLEEP_LENGTH).await;
+    }
+}
+
+/// Remove delivery ids older than the configured retention window.
+async fn expire_deliveries() -> Result<(), DbErr> {
+    let config = get_config();
+    let cutoff = Utc::now() - Duration::hours(config.delivery_retention_hours());
+
+    ProcessedDeliveriesEntity::delete_many()
+        .filter(ProcessedDeliveriesColumn::ReceivedAt.lt(cutoff))
+        .exec(&get_db().await?)
+        .await?;
+    Ok(())
+}
+
+/// Store the raw delivery and the response it was given in `webhook_deliveries`, then prune
+/// the table down to the configured limit.
+///
+/// The body of a delivery whose signature could not be verified is not kept, as it may be
+/// forged and must never be dispatched.
+pub(crate) async fn store_delivery(
+    headers: &HeaderMap,
+    body: &str,
+    response: &WebhookResponse,
+) -> Result<(), DbErr> {
+    let db = get_db().await?;
+
+    let header_value = |key: &str| {
+        headers
+            .get(key)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string())
+    };
+
+    let header_map = headers
+        .iter()
+        .map(|(k, v)| (k.to_string(), String::from_utf8_lossy(v.as_bytes()).into()))
+        .collect::<BTreeMap<String, String>>();
+    let (outcome, detail) = response.outcome();
+    let body = match outcome {
+        WebhookDeliveryOutcome::Unauthorized => "",
+        _ => body,
+    };
+
+    let row = WebhookDeliveryActiveModel {
+        delivery_id: Set(header_value(GITHUB_DELIVERY_KEY)),
+        event_type: Set(header_value(GITHUB_EVENT_KEY)),
+        headers: Set(serde_json::to_string(&header_map).unwrap()),
+        body: Set(body.into()),
+        outcome: Set(outcome),
+        detail: Set(detail),
+        received_at: Set(Utc::now()),
+        ..Default::default()
+    };
+    row.insert(&db).await?;
+
+    prune_stored_deliveries().await
+}
+
+/// Delete all but the most recent stored deliveries.
+async fn prune_stored_deliveries() -> Result<(), DbErr> {
+    let config = get_config();
+    let db = get_db().await?;
+
+    let oldest_kept: Option<i32> = WebhookDeliveriesEntity::find()
+        .select_only()
+        .column(WebhookDeliveriesColumn::Id)
+        .order_by_desc(WebhookDeliveriesColumn::Id)
+        .offset(config.stored_delivery_limit().saturating_sub(1))
+        .into_tuple()
+        .one(&db)
+        .await?;
+
+    if let Some(id) = oldest_kept {
+        WebhookDeliveriesEntity::delete_many()
+            .filter(WebhookDeliveriesColumn::Id.lt(id))
+            .exec(&db)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Run the stored delivery `id` through the same dispatch path as a live delivery.
+///
+/// The signature is verified again against the stored headers, so only deliveries that really
+/// came from Github are replayed. The delivery id check is skipped, as the replay is requested
+/// by the operator.
+pub(crate) async fn replay_delivery(app_user: &User, id: i32) -> Result<(), Box<dyn Error>> {
+    let config = get_config();
+    let db = get_db().await?;
+
+    let delivery = match WebhookDeliveriesEntity::find_by_id(id).one(&db).await? {
+        Some(d) => d,
+        None => return Err(format!("No stored delivery with id {id}").into()),
+    };
+
+    if delivery.outcome == WebhookDeliveryOutcome::Unauthorized {
+        return Err(
+            format!("Stored delivery {id} was not authorized and cannot be replayed").into(),
+        );
+    }
+
+    let event_type = match &delivery.event_type {
+        Some(et) => et,
+        None => return Err(format!("Stored delivery {id} has no event type").into()),
+    };
+
+    let stored_headers: BTreeMap<String, String> = serde_json::from_str(&delivery.headers)?;
+    let mut headers = HeaderMap::new();
+    for (key, value) in stored_headers {
+        headers.insert(HeaderName::try_from(key)?, HeaderValue::try_from(value)?);
+    }
+    if let Err(e) = verify_delivery(&headers, &delivery.body) {
+        return Err(format!("Stored delivery {id} cannot be replayed. {e}").into());
+    }
+
+    info(
+        format!("Replaying {event_type} delivery {id}"),
+        Some(&config),
+    );
+
+    match parse_payload(event_type, &delivery.body)? {
+        Some(payload) => dispatch(app_user, payload).await?,
+        None => info(format!("Ignoring event {event_type}"), Some(&config)),
+    }
+
+    Ok(())
+}
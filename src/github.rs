@@ -11,7 +11,10 @@ use reqwest::{IntoUrl, Response, StatusCode};
 use serde::{Deserialize, Serialize};
 use std::{collections::BTreeMap, error::Error};
 
-use self::model::{pulls::PullRequest, Issue};
+use self::model::{
+    pulls::{CommitAuthor, PullRequest, PullRequestCommit},
+    Issue, Reaction,
+};
 
 #[derive(Debug)]
 pub(crate) enum GithubClientError {
@@ -59,6 +62,9 @@ const GITHUB_ACCEPT_TYPE: &str = "application/vnd.github+json";
 const GITHUB_API_VERSION_HEADER_KEY: &str = "X-GitHub-Api-Version";
 const GITHUB_API_VERSION_HEADER_VALUE: &str = "2022-11-28";
 
+/// The most commits Github lists for a pull request.
+pub(crate) const PULL_REQUEST_COMMIT_LIMIT: usize = 250;
+
 pub(crate) struct GithubClient<'a> {
     reqwest: reqwest::Client,
     access_token: &'a str, // default_headers: &'a [(&'a str, &'a str)],
@@ -193,6 +199,49 @@ impl<'a> GithubClient<'a> {
         self.reqwest.execute(request).await
     }
 
+    async fn patch<U, T>(
+        &self,
+        route: U,
+        body: Option<&T>,
+        bearer_override: Option<&str>,
+    ) -> Result<reqwest::Response, reqwest::Error>
+    where
+        U: IntoUrl,
+        T: Serialize + ?Sized,
+    {
+        let bearer = format!(
+            "Bearer {}",
+            if let Some(bo) = bearer_override {
+                bo
+            } else {
+                self.access_token
+            }
+        );
+        let default_headers = &[
+            ("Authorization", bearer.as_str()),
+            ("Accept", GITHUB_ACCEPT_TYPE),
+            (
+                GITHUB_API_VERSION_HEADER_KEY,
+                GITHUB_API_VERSION_HEADER_VALUE,
+            ),
+        ];
+
+        let mut builder = self.reqwest.patch(route);
+        for (k, v) in default_headers {
+            builder = builder.header(*k, *v)
+        }
+
+        let request = if let Some(b) = body {
+            builder
+                .body(serde_json::to_string(b).unwrap())
+                .build()
+                .unwrap()
+        } else {
+            builder.build().unwrap()
+        };
+        self.reqwest.execute(request).await
+    }
+
     async fn gh_app_post<U, T>(
         &mut self,
         route: U,
@@ -376,6 +425,65 @@ impl<'a> GithubClient<'a> {
         }
     }
 
+    /// React to the issue comment `comment_id`. Adding a reaction the bot has already made is
+    /// not an error.
+    pub(crate) async fn create_issue_comment_reaction(
+        &self,
+        owner: &str,
+        repo: &str,
+        comment_id: u64,
+        content: Reaction,
+    ) -> Result<(), GithubClientError> {
+        let route = format!(
+            "{GITHUB_API_ROOT}/repos/{owner}/{repo}/issues/comments/{comment_id}/reactions"
+        );
+
+        #[derive(Serialize)]
+        struct PostReaction {
+            content: Reaction,
+        }
+
+        match self
+            .post(route, Some(&PostReaction { content }), None)
+            .await
+        {
+            Ok(r) => match r.status() {
+                StatusCode::OK | StatusCode::CREATED => Ok(()),
+                _ => Err(GithubClientError::GithubError(r)),
+            },
+            Err(e) => Err(GithubClientError::RequestError(e)),
+        }
+    }
+
+    /// React to the pull request review comment `comment_id`, in the same way as
+    /// [`GithubClient::create_issue_comment_reaction`].
+    pub(crate) async fn create_review_comment_reaction(
+        &self,
+        owner: &str,
+        repo: &str,
+        comment_id: u64,
+        content: Reaction,
+    ) -> Result<(), GithubClientError> {
+        let route =
+            format!("{GITHUB_API_ROOT}/repos/{owner}/{repo}/pulls/comments/{comment_id}/reactions");
+
+        #[derive(Serialize)]
+        struct PostReaction {
+            content: Reaction,
+        }
+
+        match self
+            .post(route, Some(&PostReaction { content }), None)
+            .await
+        {
+            Ok(r) => match r.status() {
+                StatusCode::OK | StatusCode::CREATED => Ok(()),
+                _ => Err(GithubClientError::GithubError(r)),
+            },
+            Err(e) => Err(GithubClientError::RequestError(e)),
+        }
+    }
+
     pub(crate) async fn list_issue_assignees(
         &self,
         owner: &str,
@@ -700,6 +808,414 @@ It is now in the queue for this repository"
         }
         // /repos/{owner}/{repo}/pulls/{pull_number}/merge
     }
+
+    /// Close the pull request without merging it.
+    pub(crate) async fn close_pull_request(
+        &self,
+        owner: &str,
+        repo: &str,
+        pull_number: u64,
+    ) -> Result<(), GithubClientError> {
+        let route = format!("{GITHUB_API_ROOT}/repos/{owner}/{repo}/pulls/{pull_number}");
+
+        #[derive(Serialize)]
+        struct PatchPull<'a> {
+            state: &'a str,
+        }
+
+        match self
+            .patch(route, Some(&PatchPull { state: "closed" }), None)
+            .await
+        {
+            Ok(r) => match r.status() {
+                StatusCode::OK => Ok(()),
+                _ => Err(GithubClientError::GithubError(r)),
+            },
+            Err(e) => Err(GithubClientError::RequestError(e)),
+        }
+    }
+
+    /// Get `username`'s permission on the repository: `admin`, `write`, `read` or `none`.
+    pub(crate) async fn get_collaborator_permission(
+        &self,
+        owner: &str,
+        repo: &str,
+        username: &str,
+    ) -> Result<String, GithubClientError> {
+        let route =
+            format!("{GITHUB_API_ROOT}/repos/{owner}/{repo}/collaborators/{username}/permission");
+
+        #[derive(Deserialize)]
+        struct CollaboratorPermission {
+            permission: String,
+        }
+
+        let response = match self.get(route, None).await {
+            Ok(r) => match r.status() {
+                StatusCode::OK => r,
+                StatusCode::NOT_FOUND => return Ok("none".into()),
+                _ => return Err(GithubClientError::GithubError(r)),
+            },
+            Err(e) => return Err(GithubClientError::RequestError(e)),
+        };
+
+        match serde_json::from_str::<CollaboratorPermission>(&response.text().await.unwrap()) {
+            Ok(p) => Ok(p.permission),
+            Err(e) => Err(GithubClientError::Basic(format!(
+                "Failed to read permission for {username}. {e}"
+            ))),
+        }
+    }
+
+    /// Whether `username` is an active member of the team `team_slug` in the organization `org`.
+    pub(crate) async fn is_team_member(
+        &self,
+        org: &str,
+        team_slug: &str,
+        username: &str,
+    ) -> Result<bool, GithubClientError> {
+        let route =
+            format!("{GITHUB_API_ROOT}/orgs/{org}/teams/{team_slug}/memberships/{username}");
+
+        #[derive(Deserialize)]
+        struct TeamMembership {
+            state: String,
+        }
+
+        let response = match self.get(route, None).await {
+            Ok(r) => match r.status() {
+                StatusCode::OK => r,
+                StatusCode::NOT_FOUND => return Ok(false),
+                _ => return Err(GithubClientError::GithubError(r)),
+            },
+            Err(e) => return Err(GithubClientError::RequestError(e)),
+        };
+
+        match serde_json::from_str::<TeamMembership>(&response.text().await.unwrap()) {
+            Ok(m) => Ok(m.state == "active"),
+            Err(e) => Err(GithubClientError::Basic(format!(
+                "Failed to read team membership for {username}. {e}"
+            ))),
+        }
+    }
+
+    /// Get the SHA of the commit that `branch` points to.
+    pub(crate) async fn get_branch_sha(
+        &self,
+        owner: &str,
+        repo: &str,
+        branch: &str,
+    ) -> Result<String, GithubClientError> {
+        let route = format!("{GITHUB_API_ROOT}/repos/{owner}/{repo}/git/ref/heads/{branch}");
+
+        #[derive(Deserialize)]
+        struct RefObject {
+            sha: String,
+        }
+
+        #[derive(Deserialize)]
+        struct GetRef {
+            object: RefObject,
+        }
+
+        let response = match self.get(route, None).await {
+            Ok(r) => match r.status() {
+                StatusCode::OK => r,
+                _ => return Err(GithubClientError::GithubError(r)),
+            },
+            Err(e) => return Err(GithubClientError::RequestError(e)),
+        };
+
+        match serde_json::from_str::<GetRef>(&response.text().await.unwrap()) {
+            Ok(r) => Ok(r.object.sha),
+            Err(e) => Err(GithubClientError::Basic(format!(
+                "Failed to read ref for {branch}. {e}"
+            ))),
+        }
+    }
+
+    /// Whether `head` contains the commit `base` points to, i.e. whether `base` can be
+    /// fast-forwarded to `head`.
+    pub(crate) async fn contains_commit(
+        &self,
+        owner: &str,
+        repo: &str,
+        base: &str,
+        head: &str,
+    ) -> Result<bool, GithubClientError> {
+        let route = format!("{GITHUB_API_ROOT}/repos/{owner}/{repo}/compare/{base}...{head}");
+
+        #[derive(Deserialize)]
+        struct Comparison {
+            status: String,
+        }
+
+        let response = match self.get(route, None).await {
+            Ok(r) => match r.status() {
+                StatusCode::OK => r,
+                _ => return Err(GithubClientError::GithubError(r)),
+            },
+            Err(e) => return Err(GithubClientError::RequestError(e)),
+        };
+
+        match serde_json::from_str::<Comparison>(&response.text().await.unwrap()) {
+            Ok(c) => Ok(c.status == "ahead" || c.status == "identical"),
+            Err(e) => Err(GithubClientError::Basic(format!(
+                "Failed to compare {base} with {head}. {e}"
+            ))),
+        }
+    }
+
+    /// Point `branch` at `sha`, creating the branch if it does not exist. If `force` is `false`,
+    /// the update must be a fast-forward.
+    pub(crate) async fn update_branch(
+        &self,
+        owner: &str,
+        repo: &str,
+        branch: &str,
+        sha: &str,
+        force: bool,
+    ) -> Result<(), GithubClientError> {
+        let route = format!("{GITHUB_API_ROOT}/repos/{owner}/{repo}/git/refs/heads/{branch}");
+
+        #[derive(Serialize)]
+        struct PatchRef<'a> {
+            sha: &'a str,
+            force: bool,
+        }
+
+        match self
+            .patch(route, Some(&PatchRef { sha, force }), None)
+            .await
+        {
+            Ok(r) => match r.status() {
+                StatusCode::OK => return Ok(()),
+                // Github reports a missing ref as unprocessable, so only fall through to creating
+                // the branch if it does not exist
+                StatusCode::UNPROCESSABLE_ENTITY | StatusCode::NOT_FOUND => {
+                    if self.get_branch_sha(owner, repo, branch).await.is_ok() {
+                        return Err(GithubClientError::GithubError(r));
+                    }
+                }
+                _ => return Err(GithubClientError::GithubError(r)),
+            },
+            Err(e) => return Err(GithubClientError::RequestError(e)),
+        }
+
+        let route = format!("{GITHUB_API_ROOT}/repos/{owner}/{repo}/git/refs");
+
+        #[derive(Serialize)]
+        struct PostRef<'a> {
+            #[serde(rename = "ref")]
+            ref_field: String,
+            sha: &'a str,
+        }
+
+        let body = PostRef {
+            ref_field: format!("refs/heads/{branch}"),
+            sha,
+        };
+
+        match self.post(route, Some(&body), None).await {
+            Ok(r) => match r.status() {
+                StatusCode::CREATED => Ok(()),
+                _ => Err(GithubClientError::GithubError(r)),
+            },
+            Err(e) => Err(GithubClientError::RequestError(e)),
+        }
+    }
+
+    /// List the commits on a pull request, oldest first. Github lists at most
+    /// [`PULL_REQUEST_COMMIT_LIMIT`] commits, so a longer pull request is cut short.
+    pub(crate) async fn list_pull_request_commits(
+        &self,
+        owner: &str,
+        repo: &str,
+        pull_number: u64,
+    ) -> Result<Vec<PullRequestCommit>, GithubClientError> {
+        const PER_PAGE: usize = 100;
+
+        let mut commits = Vec::new();
+        for page in 1.. {
+            let route = format!(
+                "{GITHUB_API_ROOT}/repos/{owner}/{repo}/pulls/{pull_number}/commits?per_page={PER_PAGE}&page={page}"
+            );
+
+            let response = match self.get(route, None).await {
+                Ok(r) => match r.status() {
+                    StatusCode::OK => r,
+                    _ => return Err(GithubClientError::GithubError(r)),
+                },
+                Err(e) => return Err(GithubClientError::RequestError(e)),
+            };
+
+            let listed = match serde_json::from_str::<Vec<PullRequestCommit>>(
+                &response.text().await.unwrap(),
+            ) {
+                Ok(c) => c,
+                Err(e) => {
+                    return Err(GithubClientError::Basic(format!(
+                        "Failed to read commits of pull request #{pull_number}. {e}"
+                    )))
+                }
+            };
+
+            let last_page = listed.len() < PER_PAGE;
+            commits.extend(listed);
+            if last_page || commits.len() >= PULL_REQUEST_COMMIT_LIMIT {
+                break;
+            }
+        }
+
+        Ok(commits)
+    }
+
+    /// Get the SHA of the tree of the commit `sha`.
+    pub(crate) async fn get_commit_tree(
+        &self,
+        owner: &str,
+        repo: &str,
+        sha: &str,
+    ) -> Result<String, GithubClientError> {
+        let route = format!("{GITHUB_API_ROOT}/repos/{owner}/{repo}/git/commits/{sha}");
+
+        #[derive(Deserialize)]
+        struct Tree {
+            sha: String,
+        }
+
+        #[derive(Deserialize)]
+        struct GitCommit {
+            tree: Tree,
+        }
+
+        let response = match self.get(route, None).await {
+            Ok(r) => match r.status() {
+                StatusCode::OK => r,
+                _ => return Err(GithubClientError::GithubError(r)),
+            },
+            Err(e) => return Err(GithubClientError::RequestError(e)),
+        };
+
+        match serde_json::from_str::<GitCommit>(&response.text().await.unwrap()) {
+            Ok(c) => Ok(c.tree.sha),
+            Err(e) => Err(GithubClientError::Basic(format!(
+                "Failed to read commit {sha}. {e}"
+            ))),
+        }
+    }
+
+    /// Create a commit of `tree` with the given `parents`, without moving any branch. Returns
+    /// the SHA of the new commit. If `author` is `None`, Github uses the authenticated user.
+    pub(crate) async fn create_commit(
+        &self,
+        owner: &str,
+        repo: &str,
+        message: &str,
+        tree: &str,
+        parents: &[&str],
+        author: Option<&CommitAuthor>,
+    ) -> Result<String, GithubClientError> {
+        let route = format!("{GITHUB_API_ROOT}/repos/{owner}/{repo}/git/commits");
+
+        #[derive(Serialize)]
+        struct PostCommit<'a> {
+            message: &'a str,
+            tree: &'a str,
+            parents: &'a [&'a str],
+            #[serde(skip_serializing_if = "Option::is_none")]
+            author: Option<&'a CommitAuthor>,
+        }
+
+        #[derive(Deserialize)]
+        struct GitCommit {
+            sha: String,
+        }
+
+        let response = match self
+            .post(
+                route,
+                Some(&PostCommit {
+                    message,
+                    tree,
+                    parents,
+                    author,
+                }),
+                None,
+            )
+            .await
+        {
+            Ok(r) => match r.status() {
+                StatusCode::CREATED => r,
+                _ => return Err(GithubClientError::GithubError(r)),
+            },
+            Err(e) => return Err(GithubClientError::RequestError(e)),
+        };
+
+        match serde_json::from_str::<GitCommit>(&response.text().await.unwrap()) {
+            Ok(c) => Ok(c.sha),
+            Err(e) => Err(GithubClientError::Basic(format!(
+                "Failed to read created commit. {e}"
+            ))),
+        }
+    }
+
+    /// Merge `head` into the branch `base`, creating a merge commit on `base`. Returns the SHA of
+    /// the merge commit, or `None` if the merge has conflicts.
+    pub(crate) async fn create_merge_commit(
+        &self,
+        owner: &str,
+        repo: &str,
+        base: &str,
+        head: &str,
+        commit_message: &str,
+    ) -> Result<Option<String>, GithubClientError> {
+        let route = format!("{GITHUB_API_ROOT}/repos/{owner}/{repo}/merges");
+
+        #[derive(Serialize)]
+        struct PostMerge<'a> {
+            base: &'a str,
+            head: &'a str,
+            commit_message: &'a str,
+        }
+
+        #[derive(Deserialize)]
+        struct MergeCommit {
+            sha: String,
+        }
+
+        let response = match self
+            .post(
+                route,
+                Some(&PostMerge {
+                    base,
+                    head,
+                    commit_message,
+                }),
+                None,
+            )
+            .await
+        {
+            Ok(r) => match r.status() {
+                StatusCode::CREATED => r,
+                StatusCode::CONFLICT => return Ok(None),
+                StatusCode::NO_CONTENT => {
+                    return Err(GithubClientError::Basic(format!(
+                        "Nothing to merge. {base} already contains {head}"
+                    )))
+                }
+                _ => return Err(GithubClientError::GithubError(r)),
+            },
+            Err(e) => return Err(GithubClientError::RequestError(e)),
+        };
+
+        match serde_json::from_str::<MergeCommit>(&response.text().await.unwrap()) {
+            Ok(c) => Ok(Some(c.sha)),
+            Err(e) => Err(GithubClientError::Basic(format!(
+                "Failed to read merge commit. {e}"
+            ))),
+        }
+    }
 }
 
 pub(crate) async fn create_issue_comment(
@@ -6,6 +6,7 @@ use url::Url;
 pub(crate) mod pulls;
 pub(crate) mod repo;
 pub(crate) mod checks;
+pub(crate) mod statuses;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub(crate) struct User {
@@ -70,6 +71,22 @@ pub(crate) struct Comment {
     pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
+/// The reactions Github allows on a comment, named as the Reactions API names them.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Reaction {
+    #[serde(rename = "+1")]
+    ThumbsUp,
+    #[serde(rename = "-1")]
+    ThumbsDown,
+    Laugh,
+    Confused,
+    Heart,
+    Hooray,
+    Rocket,
+    Eyes,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum IssueState {
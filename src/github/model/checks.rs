@@ -22,7 +22,7 @@ pub(crate) enum CheckSuiteStatus {
     Pending,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone, PartialEq)]
 pub(crate) enum CheckSuiteConclusion {
     #[serde(rename = "success")]
     Success,
@@ -55,18 +55,37 @@ pub(crate) enum CheckSuiteConclusion {
 #[derive(Debug, Deserialize)]
 pub(crate) struct CheckSuite {
     id: u64,
-    head_branch: String,
-    head_sha: String,
-    status: Option<CheckSuiteStatus>,
-    conclusion: Option<CheckSuiteConclusion>,
+    head_branch: Option<String>,
+    pub head_sha: String,
+    pub status: Option<CheckSuiteStatus>,
+    pub conclusion: Option<CheckSuiteConclusion>,
     url: Url,
-    before: String,
-    after: String,
+    before: Option<String>,
+    after: Option<String>,
     pull_requests: Vec<CheckSuitePullRequest>,
+    pub app: Option<CheckSuiteApp>,
+}
+
+/// The Github App that created the check suite, e.g. Github Actions.
+#[derive(Debug, Deserialize)]
+pub(crate) struct CheckSuiteApp {
+    pub slug: Option<String>,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct CheckRun {
+    pub id: u64,
+    pub name: String,
+    pub head_sha: String,
+    pub status: CheckSuiteStatus,
+    pub conclusion: Option<CheckSuiteConclusion>,
+    url: Url,
 }
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct CheckSuitePullRequestHead {
+    #[serde(rename = "ref")]
     ref_name: String,
     sha: String,
     repo: CheckSuitePullRequestRepo,
@@ -74,6 +93,7 @@ pub(crate) struct CheckSuitePullRequestHead {
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct CheckSuitePullRequestBase {
+    #[serde(rename = "ref")]
     ref_name: String,
     sha: String,
     repo: CheckSuitePullRequestRepo,
@@ -1,5 +1,5 @@
 use chrono::{DateTime, Local};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use super::{repo::Repository, User};
 
@@ -8,16 +8,20 @@ pub(crate) struct PullRequest {
     pub id: u64,
     // pub repository: Repository,
     pub number: u64,
+    pub user: Option<User>,
+    pub title: Option<String>,
+    pub body: Option<String>,
     pub head: Head,
     pub base: Base,
     pub merge_commit_sha: Option<String>,
     pub assignee: Option<User>,
     pub merged_at: Option<DateTime<Local>>,
+    pub draft: Option<bool>,
 }
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct Head {
-    pub label: Option<String>,
+    pub label: String,
     #[serde(rename = "ref")]
     pub ref_field: String,
     pub sha: String,
@@ -27,7 +31,7 @@ pub(crate) struct Head {
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct Base {
-    pub label: Option<String>,
+    pub label: String,
     #[serde(rename = "ref")]
     pub ref_field: String,
     pub sha: String,
@@ -35,6 +39,27 @@ pub(crate) struct Base {
     pub repo: Option<Repository>,
 }
 
+/// A commit on a pull request, as listed by the pull request commits endpoint.
+#[derive(Debug, Deserialize)]
+pub(crate) struct PullRequestCommit {
+    pub sha: String,
+    pub commit: CommitDetails,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct CommitDetails {
+    pub message: String,
+    pub author: Option<CommitAuthor>,
+}
+
+/// The git author of a commit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct CommitAuthor {
+    pub name: String,
+    pub email: String,
+    pub date: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub(crate) enum PullRequestReviewState {
     /// A review allowing the pull request to merge.
@@ -64,7 +89,19 @@ pub(crate) enum PullRequestReviewState {
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct PullRequestReview {
-    id: u64,
+    pub id: u64,
+    pub body: Option<String>,
+
+    /// The head of the pull request when the review was submitted.
+    pub commit_id: String,
     pub state: PullRequestReviewState,
     pub user: User,
 }
+
+/// A comment on a line of a pull request's diff.
+#[derive(Debug, Deserialize)]
+pub(crate) struct PullRequestReviewComment {
+    pub id: u64,
+    pub body: String,
+    pub user: User,
+}
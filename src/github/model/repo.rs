@@ -9,5 +9,5 @@ pub(crate) struct Repository {
     pub node_id: Option<String>,
     pub name: String,
     pub full_name: String,
-    pub owner: Option<User>,
+    pub owner: User,
 }
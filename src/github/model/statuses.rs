@@ -0,0 +1,10 @@
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum StatusState {
+    Pending,
+    Success,
+    Failure,
+    Error,
+}
@@ -0,0 +1,114 @@
+use chrono::Utc;
+use entity::processed_commands::{
+    ActiveModel as ProcessedCommandActiveModel, Column as ProcessedCommandsColumn, CommandOutcome,
+    CommentKind, Entity as ProcessedCommandsEntity, Model as ProcessedCommand,
+};
+use sea_orm::{
+    sea_query::OnConflict, ActiveModelTrait, ColumnTrait, DbErr, EntityTrait, QueryFilter,
+    QueryOrder, Set,
+};
+
+use crate::{
+    db::get_db,
+    routes::{CommandContext, CommandSource},
+};
+
+impl CommandSource {
+    /// The kind and id of the comment, which together identify it.
+    fn key(&self) -> (CommentKind, u64) {
+        match self {
+            CommandSource::IssueComment { id } => (CommentKind::IssueComment, *id),
+            CommandSource::Review { id } => (CommentKind::Review, *id),
+            CommandSource::ReviewComment { id } => (CommentKind::ReviewComment, *id),
+        }
+    }
+}
+
+/// Record that `command` from the comment in `cc` is being run.
+///
+/// Returns `false` if the comment's command has already been recorded, in which case it must
+/// not be run again. As with deliveries, the check and the insert are a single statement. A
+/// command that failed is claimed again, so a redelivery of the comment retries it.
+pub(crate) async fn claim_command(cc: &CommandContext<'_>, command: &str) -> Result<bool, DbErr> {
+    let (comment_kind, comment_id) = cc.source.key();
+
+    let row = ProcessedCommandActiveModel {
+        comment_kind: Set(comment_kind),
+        comment_id: Set(comment_id),
+        command: Set(command.into()),
+        repository: Set(cc.repository.full_name.clone()),
+        number: Set(cc.number),
+        commenter: Set(cc.commenter.clone()),
+        outcome: Set(CommandOutcome::Processing),
+        processed_at: Set(Utc::now()),
+    };
+
+    let inserted = ProcessedCommandsEntity::insert(row)
+        .on_conflict(
+            OnConflict::columns([
+                ProcessedCommandsColumn::CommentKind,
+                ProcessedCommandsColumn::CommentId,
+                ProcessedCommandsColumn::Command,
+            ])
+            .update_columns([
+                ProcessedCommandsColumn::Commenter,
+                ProcessedCommandsColumn::Outcome,
+                ProcessedCommandsColumn::ProcessedAt,
+            ])
+            .action_and_where(ProcessedCommandsColumn::Outcome.eq(CommandOutcome::Failed))
+            .to_owned(),
+        )
+        .exec_without_returning(&get_db().await?)
+        .await?;
+
+    Ok(inserted == 1)
+}
+
+/// Record how a claimed command turned out.
+pub(crate) async fn finish_command(
+    cc: &CommandContext<'_>,
+    command: &str,
+    outcome: CommandOutcome,
+) -> Result<(), DbErr> {
+    let db = get_db().await?;
+    let (comment_kind, comment_id) = cc.source.key();
+
+    let key = (comment_kind, comment_id, command.to_string());
+
+    let processed = match ProcessedCommandsEntity::find_by_id(key).one(&db).await? {
+        Some(p) => p,
+        None => {
+            return Err(DbErr::RecordNotFound(format!(
+                "No processed command `{command}` for comment {comment_id}"
+            )))
+        }
+    };
+
+    let mut processed: ProcessedCommandActiveModel = processed.into();
+    processed.outcome = Set(outcome);
+    processed.update(&db).await?;
+    Ok(())
+}
+
+/// The commands issued on the pull request or issue `number`, oldest first.
+pub(crate) async fn pull_request_history(
+    repository: &str,
+    number: u64,
+) -> Result<Vec<ProcessedCommand>, DbErr> {
+    ProcessedCommandsEntity::find()
+        .filter(ProcessedCommandsColumn::Repository.eq(repository))
+        .filter(ProcessedCommandsColumn::Number.eq(number))
+        .order_by_asc(ProcessedCommandsColumn::ProcessedAt)
+        .all(&get_db().await?)
+        .await
+}
+
+/// Describe an outcome for the history.
+pub(crate) fn describe_outcome(outcome: &CommandOutcome) -> &'static str {
+    match outcome {
+        CommandOutcome::Processing => "processing",
+        CommandOutcome::Succeeded => "succeeded",
+        CommandOutcome::Failed => "failed",
+        CommandOutcome::Refused => "refused",
+    }
+}
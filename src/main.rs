@@ -13,14 +13,20 @@ use github::{model::User, GithubClient};
 use logging::{error, info};
 
 mod actions;
+mod checks;
 mod command;
 mod config;
 mod db;
+mod deliveries;
 mod github;
+mod history;
 mod logging;
 mod model;
+mod permissions;
 mod queue;
 mod routes;
+mod signature;
+mod tree;
 
 lazy_static::lazy_static! {
     static ref CONFIG: Arc<Config> = Arc::new(load_config(None).unwrap());
@@ -56,6 +62,38 @@ async fn main() -> Result<(), Box<dyn Error>> {
     // db::create_db().await?;
     db::apply_migrations().await?;
 
+    // `yad replay <id>` re-runs a stored webhook delivery and exits
+    let args = std::env::args().collect::<Vec<_>>();
+    if let Some("replay") = args.get(1).map(|a| a.as_str()) {
+        let id = match args.get(2).map(|id| id.parse::<i32>()) {
+            Some(Ok(id)) => id,
+            _ => panic!("Usage: yad replay <delivery id>"),
+        };
+
+        deliveries::replay_delivery(&state.app_user, id).await?;
+        info(format!("Replayed delivery {id}"), Some(&config));
+        return Ok(());
+    }
+
+    // `yad history <owner/repo> <number>` prints the commands issued on a pull request
+    if let Some("history") = args.get(1).map(|a| a.as_str()) {
+        let (repository, number) = match (args.get(2), args.get(3).map(|n| n.parse::<u64>())) {
+            (Some(repository), Some(Ok(number))) => (repository, number),
+            _ => panic!("Usage: yad history <owner/repo> <pull request number>"),
+        };
+
+        for processed in history::pull_request_history(repository, number).await? {
+            println!(
+                "{} @{}: {} ({})",
+                processed.processed_at,
+                processed.commenter,
+                processed.command,
+                history::describe_outcome(&processed.outcome)
+            );
+        }
+        return Ok(());
+    }
+
     // build our application with a single route
     let app = Router::new()
         .route("/", get(|| async { "Hello, World!" }))
@@ -63,6 +101,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
         .with_state(state);
 
     tokio::spawn(queue::queue_server());
+    tokio::spawn(deliveries::expiry_server());
 
     start(app).await;
 
@@ -0,0 +1,134 @@
+use lazy_static::lazy_static;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    config::{get_config, Config},
+    github::{GithubClient, GithubClientError},
+    logging::error,
+};
+
+lazy_static! {
+    /// Looked up review rights, keyed by `owner/repo` and user name, with the time of the lookup.
+    static ref REVIEWER_CACHE: Mutex<HashMap<(String, String), (bool, Instant)>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Collaborator permissions that grant review rights when a repository has no allowlist.
+const REVIEWER_PERMISSIONS: &[&str] = &["admin", "write"];
+
+/// Whether `user` has review rights on `owner/repo`, and so may issue privileged commands.
+///
+/// If the repository configures `reviewers` or `reviewer_teams`, only those users and team
+/// members are reviewers. Otherwise anyone with write access to the repository is. Lookups are
+/// cached for the configured time, and a failed lookup denies review rights.
+pub(crate) async fn is_reviewer(owner: &str, repo: &str, user: &str) -> bool {
+    let config = get_config();
+    let key = (format!("{owner}/{repo}"), user.to_string());
+    let max_age = Duration::from_secs(config.permission_cache_seconds());
+
+    if let Some(reviewer) = cached_reviewer(&key, max_age) {
+        return reviewer;
+    }
+
+    match lookup_reviewer(&config, owner, repo, user).await {
+        Ok(reviewer) => {
+            REVIEWER_CACHE
+                .lock()
+                .unwrap()
+                .insert(key, (reviewer, Instant::now()));
+            reviewer
+        }
+        Err(e) => {
+            error(
+                format!("Failed to look up review rights of @{user} on {owner}/{repo}. {e}"),
+                Some(&config),
+            );
+            false
+        }
+    }
+}
+
+fn cached_reviewer(key: &(String, String), max_age: Duration) -> Option<bool> {
+    match REVIEWER_CACHE.lock().unwrap().get(key) {
+        Some((reviewer, looked_up)) if looked_up.elapsed() < max_age => Some(*reviewer),
+        _ => None,
+    }
+}
+
+/// Whether `user` is in the `reviewers` allowlist. Github user names are case-insensitive.
+fn is_allowlisted(reviewers: &[String], user: &str) -> bool {
+    reviewers.iter().any(|r| r.eq_ignore_ascii_case(user))
+}
+
+/// Whether `user` is the `delegate` of a pull request. Github user names are case-insensitive.
+pub(crate) fn is_delegate(delegate: Option<&str>, user: &str) -> bool {
+    delegate.is_some_and(|d| d.eq_ignore_ascii_case(user))
+}
+
+async fn lookup_reviewer(
+    config: &Config,
+    owner: &str,
+    repo: &str,
+    user: &str,
+) -> Result<bool, GithubClientError> {
+    let client = GithubClient::new(config.access_token());
+    let (reviewers, teams) = match config.repo(owner, repo) {
+        Some(r) => (r.reviewers(), r.reviewer_teams()),
+        None => (&[][..], &[][..]),
+    };
+
+    if reviewers.is_empty() && teams.is_empty() {
+        let permission = client
+            .get_collaborator_permission(owner, repo, user)
+            .await?;
+        return Ok(REVIEWER_PERMISSIONS.contains(&permission.as_str()));
+    }
+
+    if is_allowlisted(reviewers, user) {
+        return Ok(true);
+    }
+
+    for team in teams {
+        if client.is_team_member(owner, team, user).await? {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cached_reviewer, is_allowlisted, is_delegate, REVIEWER_CACHE};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn allowlist_ignores_case() {
+        let reviewers = vec!["Alice".to_string()];
+        assert!(is_allowlisted(&reviewers, "alice"));
+        assert!(!is_allowlisted(&reviewers, "bob"));
+    }
+
+    #[test]
+    fn delegate_ignores_case() {
+        assert!(is_delegate(Some("Alice"), "alice"));
+        assert!(!is_delegate(Some("Alice"), "bob"));
+        assert!(!is_delegate(None, "alice"));
+    }
+
+    #[test]
+    fn cache_expires() {
+        let key = ("xva-lang/cache-test".to_string(), "alice".to_string());
+        REVIEWER_CACHE
+            .lock()
+            .unwrap()
+            .insert(key.clone(), (true, Instant::now()));
+
+        assert_eq!(cached_reviewer(&key, Duration::from_secs(300)), Some(true));
+        assert_eq!(cached_reviewer(&key, Duration::ZERO), None);
+    }
+}
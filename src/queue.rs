@@ -1,15 +1,31 @@
 use entity::{
-    merges::{Entity as MergesEntity, Model as MergesModel},
-    pull_requests::{Entity as PullRequestsEntity, Model as PullRequestsModel},
+    merges::{Column as MergesColumn, Entity as MergesEntity, MergeStatus, Model as MergesModel},
+    pull_requests::{
+        Column as PullRequestsColumn, Entity as PullRequestsEntity, Model as PullRequestsModel,
+        PullRequestStatus, RollupMode,
+    },
 };
-use sea_orm::{ActiveModelTrait, DbErr, EntityTrait, Set};
-use std::time::Duration;
+use sea_orm::{
+    sea_query::OnConflict, ActiveModelTrait, ColumnTrait, DbErr, EntityTrait, QueryFilter,
+    QueryOrder, Set,
+};
+use std::{cmp::Ordering, collections::BTreeMap, time::Duration};
 
 use crate::{
-    config::{get_config, Config},
+    actions::{
+        clear_pull_request_approval, set_pull_request_merge_commit, set_pull_request_status,
+        set_pull_request_try, set_pull_request_try_commit,
+    },
+    checks::{get_checks_state, get_statuses_state, ChecksState},
+    config::{
+        get_config,
+        repo::{MergeMethod, DEFAULT_AUTO_BRANCH, DEFAULT_TRY_BRANCH},
+        Config,
+    },
     db::get_db,
-    github::GithubClient,
+    github::{GithubClient, GithubClientError, PULL_REQUEST_COMMIT_LIMIT},
     logging::{error, info},
+    tree::get_tree_threshold,
 };
 
 pub(crate) async fn queue_server() {
@@ -18,61 +34,853 @@ pub(crate) async fn queue_server() {
 
     let gh_client = GithubClient::new(&config.access_token());
     loop {
-        handle_merge_queue(&gh_client, &config).await.unwrap();
+        // A database error is most likely transient, e.g. a locked database, so keep going
+        if let Err(e) = handle_merge_queue(&gh_client, &config).await {
+            error(
+                format!("Failed to advance the merge queue. {e}"),
+                Some(&config),
+            );
+        }
+        if let Err(e) = handle_try_builds(&gh_client, &config).await {
+            error(format!("Failed to run try builds. {e}"), Some(&config));
+        }
         tokio::time::sleep(SLEEP_LENGTH).await;
     }
 }
 
 async fn handle_merge_queue(client: &GithubClient<'_>, config: &Config) -> Result<(), DbErr> {
-    let db = get_db().await?;
-
     let pulls_with_merges: Vec<(PullRequestsModel, Vec<MergesModel>)> = PullRequestsEntity::find()
         .find_with_related(MergesEntity)
         .all(&get_db().await?)
         .await?;
 
+    let mut queues: BTreeMap<String, Vec<(PullRequestsModel, MergesModel)>> = BTreeMap::new();
     for (pr, merges) in pulls_with_merges {
-        if merges.len() == 0 || merges.len() > 1 {
+        if pr.draft {
             continue;
         }
 
-        let parts = pr.repository.split("/").collect::<Vec<_>>();
-        let (owner, repo) = (parts.get(0).unwrap(), parts.get(1).unwrap());
-        let pull_number = pr.number;
-        let head_ref = pr.head_ref;
-        let approver = pr.approved_by.unwrap();
+        if let Some(merge) = merges.into_iter().next() {
+            queues
+                .entry(pr.repository.clone())
+                .or_default()
+                .push((pr, merge));
+        }
+    }
+
+    // Each repository is advanced on its own, so a failure in one does not hold up the others
+    for (repository, mut queue) in queues {
+        queue.sort_by(|(a, _), (b, _)| queue_order(a, b));
+
+        if let Err(e) = advance_queue(client, config, queue).await {
+            error(
+                format!("Failed to advance the merge queue for {repository}. {e}"),
+                Some(config),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// The order pull requests are tested in: highest priority first, then the earliest approval,
+/// then the lowest pull request number.
+fn queue_order(a: &PullRequestsModel, b: &PullRequestsModel) -> Ordering {
+    b.priority
+        .cmp(&a.priority)
+        .then_with(|| match (a.approved_at, b.approved_at) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        })
+        .then_with(|| a.number.cmp(&b.number))
+}
+
+/// Whether the pull request can be merged given the repository's merge threshold. While the tree
+/// is closed only pull requests with a priority of at least the threshold are merged.
+fn tree_allows(pr: &PullRequestsModel, threshold: Option<i32>) -> bool {
+    match threshold {
+        Some(t) => pr.priority >= t,
+        None => true,
+    }
+}
+
+/// Move a repository's sorted queue along by one step. The auto branch is shared by every pull
+/// request in a repository, so only one batch is tested at a time: while a test is in progress it
+/// is checked on, otherwise the test for the next batch the tree allows is started.
+async fn advance_queue(
+    client: &GithubClient<'_>,
+    config: &Config,
+    queue: Vec<(PullRequestsModel, MergesModel)>,
+) -> Result<(), DbErr> {
+    let (started, waiting): (Vec<_>, Vec<_>) = queue
+        .into_iter()
+        .filter(|(_, m)| m.status != MergeStatus::Failed)
+        .partition(|(_, m)| m.status == MergeStatus::Started);
+
+    if !started.is_empty() {
+        return finish_merge(client, config, started).await;
+    }
+
+    let threshold = match waiting.first() {
+        Some((pr, _)) => get_tree_threshold(config, &pr.repository).await?,
+        None => return Ok(()),
+    };
+
+    let batch = next_batch(
+        waiting
+            .into_iter()
+            .filter(|(pr, _)| tree_allows(pr, threshold))
+            .collect(),
+    );
+    if !batch.is_empty() {
+        start_merge(client, config, batch).await?;
+    }
+
+    Ok(())
+}
+
+/// Pick the pull requests to test next from the sorted waiting pull requests. If the first in
+/// line is marked `rollup=always`, every waiting `rollup=always` pull request with the same base
+/// branch is rolled up with it and tested at once. Otherwise the first is tested alone.
+fn next_batch(
+    waiting: Vec<(PullRequestsModel, MergesModel)>,
+) -> Vec<(PullRequestsModel, MergesModel)> {
+    let (base_ref, rollup) = match waiting.first() {
+        Some((pr, _)) => (pr.base_ref.clone(), pr.rollup == RollupMode::Always),
+        None => return vec![],
+    };
+
+    if rollup {
+        waiting
+            .into_iter()
+            .filter(|(pr, _)| pr.rollup == RollupMode::Always && pr.base_ref == base_ref)
+            .collect()
+    } else {
+        waiting.into_iter().take(1).collect()
+    }
+}
+
+/// Split a pull request's `owner/repo` repository name. A name without an owner has an empty
+/// repository part.
+fn split_repository(repository: &str) -> (&str, &str) {
+    repository.split_once('/').unwrap_or((repository, ""))
+}
+
+/// Get the branch name from a Github label of the form `owner:branch`.
+fn branch_from_label(label: &str) -> &str {
+    match label.split_once(":") {
+        Some((_, branch)) => branch,
+        None => label,
+    }
+}
+
+fn merge_commit_message(pr: &PullRequestsModel, rollup: bool) -> String {
+    format!(
+        "{} merge of #{} - {}, r={}",
+        if rollup { "Rollup" } else { "Auto" },
+        pr.number,
+        pr.head_ref,
+        pr.approved_by.as_deref().unwrap_or_default()
+    )
+}
+
+/// List the pull requests in a batch as `#1, #2, #3`.
+fn describe_batch(batch: &[(PullRequestsModel, MergesModel)]) -> String {
+    batch
+        .iter()
+        .map(|(pr, _)| format!("#{}", pr.number))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
 
-        info(
-            format!("Starting merge for pull request #{pull_number}"),
+async fn post_comment(
+    client: &GithubClient<'_>,
+    config: &Config,
+    pr: &PullRequestsModel,
+    body: &str,
+) {
+    let (owner, repo) = split_repository(&pr.repository);
+    if let Err(e) = client
+        .create_issue_comment(owner, repo, pr.number, body)
+        .await
+    {
+        error(
+            format!(
+                "Failed to create issue comment on pull request #{}. {e}",
+                pr.number
+            ),
             Some(config),
         );
-        //             "update merges set status = ?1 where pull_request_id = ?2",
-
-        for merge in merges {
-            let mut update_merge: entity::merges::ActiveModel = merge.clone().into();
-            update_merge.status = Set(entity::merges::MergeStatus::Started);
-            update_merge = match update_merge.update(&db).await {
-                Ok(r) => r.into(),
-                Err(e) => return Err(e),
+    }
+}
+
+/// Whether the pull request's approval was given for the commit `head_sha`.
+fn approved_for(pr: &PullRequestsModel, head_sha: &str) -> bool {
+    pr.approved_sha.as_deref() == Some(head_sha)
+}
+
+/// Check that the pull request's head on Github is still the commit that was approved. If it is
+/// not, the approval is reset, which takes the pull request out of the queue, and the bot says so.
+///
+/// Returns `true` if the pull request may be merged. The head is checked against Github rather
+/// than the database, so a push whose webhook has not been handled yet is still caught.
+async fn check_approval(
+    client: &GithubClient<'_>,
+    config: &Config,
+    pr: &PullRequestsModel,
+) -> Result<bool, DbErr> {
+    let (owner, repo) = split_repository(&pr.repository);
+    let head_sha = match client
+        .get_pull_request_from_issue_number(owner, repo, pr.number)
+        .await
+    {
+        Ok(Some(live)) => live.head.sha,
+        Ok(None) => return Ok(false),
+        Err(e) => {
+            error(
+                format!(
+                    "Failed to get the head of pull request #{} in {}. {e}",
+                    pr.number, pr.repository
+                ),
+                Some(config),
+            );
+            return Ok(false);
+        }
+    };
+
+    if approved_for(pr, &head_sha) {
+        return Ok(true);
+    }
+
+    info(
+        format!(
+            "Approval for pull request #{} in {} reset, head is {head_sha}",
+            pr.number, pr.repository
+        ),
+        Some(config),
+    );
+    clear_pull_request_approval(pr.id).await?;
+
+    let body = match &pr.approved_sha {
+        Some(approved_sha) => format!(":warning: Approval reset: the head of this pull request moved from the approved commit {approved_sha} to {head_sha}. It needs to be approved again."),
+        None => format!(":warning: Approval reset: the approved commit is unknown and the head of this pull request is {head_sha}. It needs to be approved again."),
+    };
+    post_comment(client, config, pr, &body).await;
+
+    Ok(false)
+}
+
+async fn set_merge_status(merge: MergesModel, status: MergeStatus) -> Result<(), DbErr> {
+    let mut update_merge: entity::merges::ActiveModel = merge.into();
+    update_merge.status = Set(status);
+    update_merge.update(&get_db().await?).await?;
+    Ok(())
+}
+
+/// Force `branch` to the current head of `base_branch`, ready for a pull request to be merged
+/// into it. Returns the SHA the branch now points to, or `None` if it could not be reset.
+async fn reset_branch(
+    client: &GithubClient<'_>,
+    config: &Config,
+    owner: &str,
+    repo: &str,
+    base_branch: &str,
+    branch: &str,
+) -> Option<String> {
+    let base_sha = match client.get_branch_sha(owner, repo, base_branch).await {
+        Ok(sha) => sha,
+        Err(e) => {
+            error(
+                format!("Failed to get the head of {base_branch} in {owner}/{repo}. {e}"),
+                Some(config),
+            );
+            return None;
+        }
+    };
+
+    if let Err(e) = client
+        .update_branch(owner, repo, branch, &base_sha, true)
+        .await
+    {
+        error(
+            format!("Failed to reset {branch} in {owner}/{repo}. {e}"),
+            Some(config),
+        );
+        return None;
+    }
+
+    Some(base_sha)
+}
+
+/// The merge method for the pull request: squashed if it is marked with `squash`, otherwise the
+/// repository's default.
+fn merge_method(config: &Config, pr: &PullRequestsModel) -> MergeMethod {
+    if pr.squash {
+        return MergeMethod::Squash;
+    }
+
+    let (owner, repo) = split_repository(&pr.repository);
+    match config.repo(owner, repo) {
+        Some(r) => r.merge_method(),
+        None => MergeMethod::Merge,
+    }
+}
+
+/// The message of a squashed commit: the pull request's title and description, followed by the
+/// approver in the same `r=` form as merge commits.
+fn squash_commit_message(pr: &PullRequestsModel, title: &str, body: &str) -> String {
+    let mut message = format!("{title} (#{})", pr.number);
+    if !body.trim().is_empty() {
+        message.push_str("\n\n");
+        message.push_str(body.trim());
+    }
+    message.push_str(&format!(
+        "\n\nr={}",
+        pr.approved_by.as_deref().unwrap_or_default()
+    ));
+    message
+}
+
+/// Apply the pull request on top of `head_sha`, the current head of `branch`, with `method`.
+/// Returns the new head of `branch`, or `None` if the pull request does not merge cleanly.
+///
+/// Squashed and rebased commits are built from the trees of merge commits Github creates on
+/// `branch`, which is then forced to the rewritten commits.
+async fn apply_pull_request(
+    client: &GithubClient<'_>,
+    branch: &str,
+    head_sha: &str,
+    pr: &PullRequestsModel,
+    method: MergeMethod,
+    rollup: bool,
+) -> Result<Option<String>, GithubClientError> {
+    let (owner, repo) = split_repository(&pr.repository);
+    let message = merge_commit_message(pr, rollup);
+    let approved_sha = pr.approved_sha.as_deref().unwrap_or(&pr.head_commit_id);
+
+    let rewritten = match method {
+        MergeMethod::Merge => {
+            return client
+                .create_merge_commit(owner, repo, branch, approved_sha, &message)
+                .await
+        }
+        MergeMethod::Squash => {
+            let merge_sha = match client
+                .create_merge_commit(owner, repo, branch, approved_sha, &message)
+                .await?
+            {
+                Some(sha) => sha,
+                None => return Ok(None),
             };
 
-            if let Err(e) = client
-                .merge_pull(owner, repo, pr.number as u64, &head_ref, &approver)
+            let (title, body) = match client
+                .get_pull_request_from_issue_number(owner, repo, pr.number)
+                .await?
+            {
+                Some(p) => (p.title.unwrap_or_default(), p.body.unwrap_or_default()),
+                None => {
+                    return Err(GithubClientError::Basic(format!(
+                        "No pull request #{}",
+                        pr.number
+                    )))
+                }
+            };
+
+            // The squashed commit is credited to the author of the pull request's first commit
+            let commits = client
+                .list_pull_request_commits(owner, repo, pr.number)
+                .await?;
+            let author = commits.first().and_then(|c| c.commit.author.as_ref());
+
+            let tree = client.get_commit_tree(owner, repo, &merge_sha).await?;
+            client
+                .create_commit(
+                    owner,
+                    repo,
+                    &squash_commit_message(pr, &title, &body),
+                    &tree,
+                    &[head_sha],
+                    author,
+                )
+                .await?
+        }
+        MergeMethod::Rebase => {
+            let commits = client
+                .list_pull_request_commits(owner, repo, pr.number)
+                .await?;
+            if commits.len() >= PULL_REQUEST_COMMIT_LIMIT {
+                return Err(GithubClientError::Basic(format!(
+                    "Github lists at most {PULL_REQUEST_COMMIT_LIMIT} commits of a pull request, so one with more cannot be rebased."
+                )));
+            }
+
+            // Merging each commit in turn gives the tree of that commit replayed on the branch
+            let mut rebased = head_sha.to_string();
+            for commit in &commits {
+                let merge_sha = match client
+                    .create_merge_commit(owner, repo, branch, &commit.sha, &message)
+                    .await?
+                {
+                    Some(sha) => sha,
+                    None => {
+                        client
+                            .update_branch(owner, repo, branch, head_sha, true)
+                            .await?;
+                        return Ok(None);
+                    }
+                };
+
+                let tree = client.get_commit_tree(owner, repo, &merge_sha).await?;
+                rebased = client
+                    .create_commit(
+                        owner,
+                        repo,
+                        &commit.commit.message,
+                        &tree,
+                        &[&rebased],
+                        commit.commit.author.as_ref(),
+                    )
+                    .await?;
+            }
+            rebased
+        }
+    };
+
+    client
+        .update_branch(owner, repo, branch, &rewritten, true)
+        .await?;
+    Ok(Some(rewritten))
+}
+
+/// Combine the required checks and commit statuses of the `owner/repo` repository for `sha`.
+async fn get_required_state(
+    config: &Config,
+    repository: &str,
+    sha: &str,
+) -> Result<ChecksState, DbErr> {
+    let (owner, repo) = split_repository(repository);
+    let (required_checks, required_statuses) = match config.repo(owner, repo) {
+        Some(r) => (r.checks(), r.statuses()),
+        None => (&[][..], &[][..]),
+    };
+
+    Ok(get_checks_state(repository, sha, required_checks)
+        .await?
+        .and(get_statuses_state(repository, sha, required_statuses).await?))
+}
+
+/// Start testing a batch of queued pull requests: reset the auto branch to the current base
+/// branch and merge the approved commit of each pull request into it in turn. The checks then run
+/// on the resulting merge commit. A pull request whose head has moved since it was approved has
+/// its approval reset instead.
+///
+/// In a rollup, a pull request that fails to merge is left out and stays in the queue to be tested
+/// on its own later. Returns `true` if a test was started.
+async fn start_merge(
+    client: &GithubClient<'_>,
+    config: &Config,
+    batch: Vec<(PullRequestsModel, MergesModel)>,
+) -> Result<bool, DbErr> {
+    // Only the commits that were approved are merged
+    let mut approved = Vec::new();
+    for (pr, merge) in batch {
+        if check_approval(client, config, &pr).await? {
+            approved.push((pr, merge));
+        }
+    }
+    let batch = approved;
+    if batch.is_empty() {
+        return Ok(false);
+    }
+
+    let rollup = batch.len() > 1;
+    let (first, _) = &batch[0];
+    let repository = first.repository.clone();
+    let (owner, repo) = split_repository(&repository);
+    let base_branch = branch_from_label(&first.base_ref).to_string();
+    let auto_branch = match config.repo(owner, repo) {
+        Some(r) => r.auto_branch(),
+        None => DEFAULT_AUTO_BRANCH,
+    };
+
+    info(
+        format!(
+            "Starting merge for pull request(s) {} in {owner}/{repo}",
+            describe_batch(&batch)
+        ),
+        Some(config),
+    );
+
+    let mut head_sha =
+        match reset_branch(client, config, owner, repo, &base_branch, auto_branch).await {
+            Some(sha) => sha,
+            None => return Ok(false),
+        };
+
+    let mut merged = Vec::new();
+    let mut merge_sha = None;
+    for (pr, merge) in batch {
+        let pull_number = pr.number;
+
+        match apply_pull_request(
+            client,
+            auto_branch,
+            &head_sha,
+            &pr,
+            merge_method(config, &pr),
+            rollup,
+        )
+        .await
+        {
+            Ok(Some(sha)) => {
+                head_sha = sha.clone();
+                merge_sha = Some(sha);
+                merged.push((pr, merge));
+            }
+            Ok(None) if rollup => info(
+                format!(
+                    "Leaving pull request #{pull_number} out of the rollup due to a merge conflict"
+                ),
+                Some(config),
+            ),
+            Ok(None) => {
+                set_merge_status(merge, MergeStatus::Failed).await?;
+                post_comment(
+                    client,
+                    config,
+                    &pr,
+                    &format!(":lock: Merge conflict with {base_branch}. This pull request needs to be rebased."),
+                )
+                .await;
+            }
+            Err(e) => {
+                error(
+                    format!("Failed to merge pull request #{pull_number} into {auto_branch}. {e}"),
+                    Some(config),
+                );
+                set_merge_status(merge, MergeStatus::Failed).await?;
+
+                // Basic errors carry a readable reason, such as too many commits to rebase
+                if let GithubClientError::Basic(reason) = &e {
+                    post_comment(
+                        client,
+                        config,
+                        &pr,
+                        &format!(":x: This pull request could not be merged. {reason}"),
+                    )
+                    .await;
+                }
+
+                // Undo anything applied before the failure, so the rest of a rollup applies cleanly
+                if let Err(e) = client
+                    .update_branch(owner, repo, auto_branch, &head_sha, true)
+                    .await
+                {
+                    error(
+                        format!("Failed to reset {auto_branch} in {owner}/{repo}. {e}"),
+                        Some(config),
+                    );
+                    return Ok(false);
+                }
+            }
+        }
+    }
+
+    let merge_sha = match merge_sha {
+        Some(sha) => sha,
+        None => return Ok(false),
+    };
+
+    let rolled_up = describe_batch(&merged);
+    for (pr, merge) in merged {
+        set_pull_request_merge_commit(pr.id, &merge_sha).await?;
+        set_merge_status(merge, MergeStatus::Started).await?;
+
+        let body = if rollup {
+            format!(":hourglass: Testing rollup of {rolled_up} with merge {merge_sha}...")
+        } else {
+            format!(
+                ":hourglass: Testing commit {} with merge {merge_sha}...",
+                pr.head_commit_id
+            )
+        };
+        post_comment(client, config, &pr, &body).await;
+    }
+
+    Ok(true)
+}
+
+/// Check on a batch of pull requests whose merge commit is being tested. Once every required
+/// check has succeeded the base branch is fast-forwarded to the merge commit, so the base branch
+/// only ever points at tested commits, and the pull requests in the batch are merged together.
+async fn finish_merge(
+    client: &GithubClient<'_>,
+    config: &Config,
+    batch: Vec<(PullRequestsModel, MergesModel)>,
+) -> Result<(), DbErr> {
+    let rollup = batch.len() > 1;
+    let (first, _) = &batch[0];
+    let repository = first.repository.clone();
+    let (owner, repo) = split_repository(&repository);
+    let base_branch = branch_from_label(&first.base_ref).to_string();
+    let merge_sha = match &first.merge_commit_id {
+        Some(sha) => sha.clone(),
+        None => {
+            // The test was never fully started, so start it again
+            for (_, merge) in batch {
+                set_merge_status(merge, MergeStatus::Waiting).await?;
+            }
+            return Ok(());
+        }
+    };
+
+    let checks_state = get_required_state(config, &repository, &merge_sha).await?;
+
+    let rolled_up = describe_batch(&batch);
+    match checks_state {
+        ChecksState::Pending => Ok(()),
+        ChecksState::Failed { name, conclusion } => {
+            for (pr, merge) in batch {
+                set_merge_status(merge, MergeStatus::Failed).await?;
+
+                let body = if rollup {
+                    format!(":broken_heart: Test failed for rollup of {rolled_up}. Check `{name}` concluded `{conclusion}` on {merge_sha}.")
+                } else {
+                    format!(":broken_heart: Test failed. Check `{name}` concluded `{conclusion}` on {merge_sha}.")
+                };
+                post_comment(client, config, &pr, &body).await;
+            }
+            Ok(())
+        }
+        ChecksState::Succeeded => {
+            // A push during the test resets the approval, and dequeuing puts the rest of a
+            // rollup back in line to be tested without it
+            for (pr, _) in &batch {
+                if !check_approval(client, config, pr).await? {
+                    return Ok(());
+                }
+            }
+
+            match client
+                .update_branch(owner, repo, &base_branch, &merge_sha, false)
                 .await
             {
-                error(format!("Failed to merge pull request. {e}"), Some(&config));
+                Ok(_) => {
+                    for (pr, merge) in batch {
+                        dequeue_merge(merge.pull_request_id).await?;
+                        set_pull_request_status(pr.id, PullRequestStatus::Merged).await?;
 
-                update_merge.status = Set(entity::merges::MergeStatus::Failed);
-                update_merge.update(&db).await?;
-            } else {
-                update_merge.delete(&db).await?;
+                        let approved_by = pr.approved_by.as_deref().unwrap_or_default();
+                        let mut body = if rollup {
+                            format!(":sunny: Test successful - approved by `{approved_by}`. Pushing {merge_sha} to {base_branch} in a rollup of {rolled_up}...")
+                        } else {
+                            format!(":sunny: Test successful - approved by `{approved_by}`. Pushing {merge_sha} to {base_branch}...")
+                        };
+
+                        // Squashed and rebased commits do not contain the pull request's head,
+                        // so Github does not see it as merged and it has to be closed
+                        if merge_method(config, &pr) != MergeMethod::Merge {
+                            body.push_str(&format!(
+                                "\n\nThe changes landed in {base_branch} as {merge_sha}, so this pull request is closed."
+                            ));
+                            if let Err(e) = client.close_pull_request(owner, repo, pr.number).await
+                            {
+                                error(
+                                    format!("Failed to close pull request #{}. {e}", pr.number),
+                                    Some(config),
+                                );
+                            }
+                        }
+                        post_comment(client, config, &pr, &body).await;
+                    }
+                    Ok(())
+                }
+                // The request did not reach Github, so try the fast-forward again next time
+                Err(GithubClientError::RequestError(e)) => {
+                    error(
+                        format!("Failed to fast-forward {base_branch} to {merge_sha}. {e}"),
+                        Some(config),
+                    );
+                    Ok(())
+                }
+                // If the base branch moved while testing, test again on the new base. Anything
+                // else, such as branch protection or a missing permission, would be refused again
+                // on every retest.
+                Err(e) => {
+                    let base_moved = match client
+                        .contains_commit(owner, repo, &base_branch, &merge_sha)
+                        .await
+                    {
+                        Ok(contained) => !contained,
+                        Err(e) => {
+                            error(
+                                format!("Failed to compare {base_branch} with {merge_sha}. {e}"),
+                                Some(config),
+                            );
+                            return Ok(());
+                        }
+                    };
+
+                    if base_moved {
+                        info(
+                            format!("{base_branch} moved while testing {merge_sha}. Retesting."),
+                            Some(config),
+                        );
+                        for (_, merge) in batch {
+                            set_merge_status(merge, MergeStatus::Waiting).await?;
+                        }
+                        return Ok(());
+                    }
+
+                    error(
+                        format!("Failed to fast-forward {base_branch} to {merge_sha}. {e}"),
+                        Some(config),
+                    );
+                    for (pr, merge) in batch {
+                        set_merge_status(merge, MergeStatus::Failed).await?;
+                        post_comment(
+                            client,
+                            config,
+                            &pr,
+                            &format!(":x: Test successful, but {base_branch} could not be fast-forwarded to {merge_sha}. Check that the bot may push to {base_branch}, then retry."),
+                        )
+                        .await;
+                    }
+                    Ok(())
+                }
             }
         }
     }
+}
+
+/// Run the requested `try` builds. Like the merge queue, each repository has a single try branch,
+/// so one try build runs at a time per repository. Try builds never touch the base branch and
+/// do not wait on the merge queue.
+async fn handle_try_builds(client: &GithubClient<'_>, config: &Config) -> Result<(), DbErr> {
+    let requested = PullRequestsEntity::find()
+        .filter(PullRequestsColumn::TryTest.eq(true))
+        .order_by_asc(PullRequestsColumn::Number)
+        .all(&get_db().await?)
+        .await?;
+
+    let mut builds: BTreeMap<String, Vec<PullRequestsModel>> = BTreeMap::new();
+    for pr in requested {
+        builds.entry(pr.repository.clone()).or_default().push(pr);
+    }
+
+    for (repository, prs) in builds {
+        let result = match prs.iter().find(|pr| pr.try_commit_id.is_some()) {
+            Some(pr) => finish_try(client, config, pr).await,
+            None => start_try(client, config, &prs[0]).await,
+        };
+
+        if let Err(e) = result {
+            error(
+                format!("Failed to run try builds for {repository}. {e}"),
+                Some(config),
+            );
+        }
+    }
 
     Ok(())
 }
 
+/// Reset the try branch to the base branch and merge the pull request's head into it.
+async fn start_try(
+    client: &GithubClient<'_>,
+    config: &Config,
+    pr: &PullRequestsModel,
+) -> Result<(), DbErr> {
+    let (owner, repo) = split_repository(&pr.repository);
+    let pull_number = pr.number;
+    let base_branch = branch_from_label(&pr.base_ref);
+    let try_branch = match config.repo(owner, repo) {
+        Some(r) => r.try_branch(),
+        None => DEFAULT_TRY_BRANCH,
+    };
+
+    info(
+        format!("Starting try build for pull request #{pull_number} in {owner}/{repo}"),
+        Some(config),
+    );
+
+    if reset_branch(client, config, owner, repo, base_branch, try_branch)
+        .await
+        .is_none()
+    {
+        return Ok(());
+    }
+
+    match client
+        .create_merge_commit(
+            owner,
+            repo,
+            try_branch,
+            &pr.head_commit_id,
+            &format!("Try merge of #{pull_number} - {}", pr.head_ref),
+        )
+        .await
+    {
+        Ok(Some(sha)) => {
+            set_pull_request_try_commit(pr.id, &sha).await?;
+            post_comment(
+                client,
+                config,
+                pr,
+                &format!(
+                    ":hourglass: Trying commit {} with merge {sha}...",
+                    pr.head_commit_id
+                ),
+            )
+            .await;
+        }
+        Ok(None) => {
+            set_pull_request_try(pr.id, false).await?;
+            post_comment(
+                client,
+                config,
+                pr,
+                &format!(":lock: Merge conflict with {base_branch}. This pull request needs to be rebased."),
+            )
+            .await;
+        }
+        Err(e) => {
+            error(
+                format!("Failed to merge pull request #{pull_number} into {try_branch}. {e}"),
+                Some(config),
+            );
+            set_pull_request_try(pr.id, false).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Report the result of a try build once its required checks have concluded.
+async fn finish_try(
+    client: &GithubClient<'_>,
+    config: &Config,
+    pr: &PullRequestsModel,
+) -> Result<(), DbErr> {
+    let try_sha = pr.try_commit_id.as_deref().unwrap_or_default();
+
+    let body = match get_required_state(config, &pr.repository, try_sha).await? {
+        ChecksState::Pending => return Ok(()),
+        ChecksState::Failed { name, conclusion } => format!(
+            ":broken_heart: Try build failed. Check `{name}` concluded `{conclusion}` on {try_sha}."
+        ),
+        ChecksState::Succeeded => {
+            format!(":sunny: Try build successful - {try_sha} passed all checks.")
+        }
+    };
+
+    set_pull_request_try(pr.id, false).await?;
+    post_comment(client, config, pr, &body).await;
+    Ok(())
+}
+
 // async fn get_approved_pull_requests(
 //     pool: &async_sqlite::Pool,
 // ) -> Result<Vec<PullRequest>, async_sqlite::Error> {
@@ -96,12 +904,230 @@ async fn handle_merge_queue(client: &GithubClient<'_>, config: &Config) -> Resul
 //     .await
 // }
 
+/// Add the pull request to the merge queue. Does nothing if it is already queued.
 pub(crate) async fn enqueue_merge(pull_request_id: u64) -> Result<(), DbErr> {
     let row = entity::merges::ActiveModel {
         pull_request_id: Set(pull_request_id),
-        status: Set(entity::merges::MergeStatus::Waiting),
+        status: Set(MergeStatus::Waiting),
     };
 
-    row.insert(&get_db().await?).await?;
+    MergesEntity::insert(row)
+        .on_conflict(
+            OnConflict::column(entity::merges::Column::PullRequestId)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(&get_db().await?)
+        .await?;
     Ok(())
 }
+
+/// Remove the pull request's row from the merge queue, if it has one.
+///
+/// If the pull request is being tested the test is cancelled. Any pull requests rolled up with it
+/// go back to waiting, so the merge commit that includes it is never pushed.
+pub(crate) async fn dequeue_merge(pull_request_id: u64) -> Result<(), DbErr> {
+    let db = get_db().await?;
+
+    let dequeued = MergesEntity::find_by_id(pull_request_id)
+        .find_also_related(PullRequestsEntity)
+        .one(&db)
+        .await?;
+
+    if let Some((merge, Some(pr))) = dequeued {
+        if merge.status == MergeStatus::Started {
+            let testing = MergesEntity::find()
+                .filter(MergesColumn::Status.eq(MergeStatus::Started))
+                .find_also_related(PullRequestsEntity)
+                .all(&db)
+                .await?;
+
+            for (other, other_pr) in testing {
+                let same_repository =
+                    other_pr.is_some_and(|other_pr| other_pr.repository == pr.repository);
+                if other.pull_request_id != pull_request_id && same_repository {
+                    set_merge_status(other, MergeStatus::Waiting).await?;
+                }
+            }
+        }
+    }
+
+    MergesEntity::delete_by_id(pull_request_id)
+        .exec(&db)
+        .await?;
+    Ok(())
+}
+
+/// Put a failed merge back in the queue.
+///
+/// Returns `true` if the pull request had a failed merge to retry.
+pub(crate) async fn retry_merge(pull_request_id: u64) -> Result<bool, DbErr> {
+    match MergesEntity::find_by_id(pull_request_id)
+        .one(&get_db().await?)
+        .await?
+    {
+        Some(merge) if merge.status == MergeStatus::Failed => {
+            set_merge_status(merge, MergeStatus::Waiting).await?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        approved_for, branch_from_label, next_batch, queue_order, split_repository,
+        squash_commit_message, tree_allows,
+    };
+    use chrono::{TimeZone, Utc};
+    use entity::{
+        merges::{MergeStatus, Model as MergesModel},
+        pull_requests::{Model as PullRequestsModel, PullRequestStatus, RollupMode},
+    };
+
+    fn queued_pull(number: u64, priority: i32, approved_at: Option<i64>) -> PullRequestsModel {
+        PullRequestsModel {
+            id: number,
+            number,
+            repository: "xva-lang/homu-test-repo".into(),
+            status: PullRequestStatus::Approved,
+            merge_commit_id: None,
+            head_commit_id: "abc123".into(),
+            head_ref: "feature".into(),
+            base_ref: "xva-lang:main".into(),
+            assignee: None,
+            approved_by: Some("reviewer".into()),
+            priority,
+            try_test: false,
+            rollup: RollupMode::Maybe,
+            squash: false,
+            delegate: None,
+            draft: false,
+            try_commit_id: None,
+            approved_sha: None,
+            approved_at: approved_at.map(|t| Utc.timestamp_opt(t, 0).unwrap()),
+        }
+    }
+
+    fn sorted_numbers(mut pulls: Vec<PullRequestsModel>) -> Vec<u64> {
+        pulls.sort_by(queue_order);
+        pulls.into_iter().map(|pr| pr.number).collect()
+    }
+
+    #[test]
+    fn higher_priority_first() {
+        let pulls = vec![queued_pull(1, 0, Some(100)), queued_pull(2, 10, Some(200))];
+        assert_eq!(sorted_numbers(pulls), vec![2, 1]);
+    }
+
+    #[test]
+    fn earlier_approval_first() {
+        let pulls = vec![
+            queued_pull(1, 0, Some(300)),
+            queued_pull(2, 0, Some(100)),
+            queued_pull(3, 0, None),
+        ];
+        assert_eq!(sorted_numbers(pulls), vec![2, 1, 3]);
+    }
+
+    #[test]
+    fn lower_number_breaks_ties() {
+        let pulls = vec![queued_pull(7, 0, Some(100)), queued_pull(3, 0, Some(100))];
+        assert_eq!(sorted_numbers(pulls), vec![3, 7]);
+    }
+
+    #[test]
+    fn branch_from_owner_label() {
+        assert_eq!(branch_from_label("xva-lang:main"), "main");
+        assert_eq!(branch_from_label("feature/nested"), "feature/nested");
+    }
+
+    #[test]
+    fn split_full_name() {
+        assert_eq!(
+            split_repository("xva-lang/homu-test-repo"),
+            ("xva-lang", "homu-test-repo")
+        );
+        assert_eq!(split_repository("homu-test-repo"), ("homu-test-repo", ""));
+    }
+
+    #[test]
+    fn closed_tree_threshold() {
+        assert!(tree_allows(&queued_pull(1, 0, None), None));
+        assert!(!tree_allows(&queued_pull(1, 0, None), Some(10)));
+        assert!(tree_allows(&queued_pull(1, 10, None), Some(10)));
+    }
+
+    fn waiting(pulls: Vec<PullRequestsModel>) -> Vec<(PullRequestsModel, MergesModel)> {
+        pulls
+            .into_iter()
+            .map(|pr| {
+                let merge = MergesModel {
+                    pull_request_id: pr.id,
+                    status: MergeStatus::Waiting,
+                };
+                (pr, merge)
+            })
+            .collect()
+    }
+
+    fn with_rollup(mut pr: PullRequestsModel, rollup: RollupMode) -> PullRequestsModel {
+        pr.rollup = rollup;
+        pr
+    }
+
+    fn batch_numbers(batch: Vec<(PullRequestsModel, MergesModel)>) -> Vec<u64> {
+        batch.into_iter().map(|(pr, _)| pr.number).collect()
+    }
+
+    #[test]
+    fn single_batch_without_rollup() {
+        let queue = waiting(vec![
+            queued_pull(1, 0, None),
+            with_rollup(queued_pull(2, 0, None), RollupMode::Always),
+        ]);
+        assert_eq!(batch_numbers(next_batch(queue)), vec![1]);
+    }
+
+    #[test]
+    fn rollup_batches_always() {
+        let mut other_base = with_rollup(queued_pull(4, 0, None), RollupMode::Always);
+        other_base.base_ref = "xva-lang:release".into();
+
+        let queue = waiting(vec![
+            with_rollup(queued_pull(1, 0, None), RollupMode::Always),
+            with_rollup(queued_pull(2, 0, None), RollupMode::Iffy),
+            with_rollup(queued_pull(3, 0, None), RollupMode::Always),
+            other_base,
+        ]);
+        assert_eq!(batch_numbers(next_batch(queue)), vec![1, 3]);
+    }
+
+    #[test]
+    fn empty_batch() {
+        assert!(next_batch(vec![]).is_empty());
+    }
+
+    #[test]
+    fn approval_for_head() {
+        let mut pr = queued_pull(1, 0, None);
+        assert!(!approved_for(&pr, "abc123"));
+
+        pr.approved_sha = Some("abc123".into());
+        assert!(approved_for(&pr, "abc123"));
+        assert!(!approved_for(&pr, "def456"));
+    }
+
+    #[test]
+    fn squash_message() {
+        assert_eq!(
+            squash_commit_message(&queued_pull(12, 0, None), "Fix the parser", "Closes #3.\n"),
+            "Fix the parser (#12)\n\nCloses #3.\n\nr=reviewer"
+        );
+        assert_eq!(
+            squash_commit_message(&queued_pull(12, 0, None), "Fix the parser", ""),
+            "Fix the parser (#12)\n\nr=reviewer"
+        );
+    }
+}
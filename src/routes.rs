@@ -1,34 +1,60 @@
-use axum::{body::Body, debug_handler, extract::State, http::HeaderMap, Json};
+use axum::{
+    body::Body,
+    debug_handler,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
 
+use chrono::Utc;
+use sea_orm::DbErr;
 use serde::Deserialize;
 use url::Url;
 
 use crate::{
     actions::{
-        approve_pull, ping, remove_assignee, save_pull_to_db, set_assignee,
-        set_pull_request_approved, set_pull_request_status,
+        approve_pull, approve_pull_as, authorize_command, clean_pull, comment_approval_reset,
+        delegate_pull, handle_pull_request_review, help, is_pull_request_saved, ping,
+        react_to_comment, remove_assignee, reply_parse_errors, request_try, retry_pull,
+        save_pull_to_db, set_assignee, set_priority, set_pull_request_approved,
+        set_pull_request_base, set_pull_request_closed, set_pull_request_draft,
+        set_pull_request_head, set_pull_request_status, set_rollup, set_squash, set_tree_closed,
+        unapprove_pull,
     },
-    command::{parse_command, Command},
+    checks::{record_check_run, record_commit_status},
+    command::{parse_command, parse_edited_command, Command, ParseError},
     config::get_config,
+    deliveries::{claim_delivery, release_delivery, store_delivery},
     github::model::{
-        checks::CheckSuite,
-        pulls::{PullRequest, PullRequestReview, PullRequestReviewState},
+        checks::{CheckRun, CheckSuite},
+        pulls::{PullRequest, PullRequestReview, PullRequestReviewComment, PullRequestReviewState},
         repo::Repository,
-        Comment, Issue, IssueCommentEventAction,
+        statuses::StatusState,
+        Comment, Issue, IssueCommentEventAction, User,
     },
-    logging::error,
+    history::{claim_command, finish_command},
+    logging::{error, info},
+    signature::{verify_signature, GITHUB_SIGNATURE_KEY},
     AppState,
 };
 
-use entity::pull_requests::{Entity, PullRequestStatus};
+use entity::{
+    processed_commands::CommandOutcome,
+    pull_requests::{Entity, PullRequestStatus},
+    webhook_deliveries::WebhookDeliveryOutcome,
+};
 
 #[derive(Debug, Deserialize)]
 #[serde(untagged)]
 pub(crate) enum EventPayload {
     IssueComment(IssueCommentPayload),
     PullRequest(PullRequestPayload),
-    // CheckSuite(CheckSuitePayload), // PullRequestReview(PullRequestReviewPayload),
+    CheckSuite(CheckSuitePayload),
+    PullRequestReview(PullRequestReviewPayload),
+    PullRequestReviewComment(PullRequestReviewCommentPayload),
     CheckRun(CheckRunPayload),
+    Status(StatusPayload),
 }
 
 #[derive(Debug, Deserialize)]
@@ -39,6 +65,27 @@ pub(crate) enum PullRequestEventAction {
 
     #[serde(rename = "closed")]
     Closed,
+
+    /// New commits were pushed to the head branch.
+    #[serde(rename = "synchronize")]
+    Synchronize,
+
+    #[serde(rename = "reopened")]
+    Reopened,
+
+    /// The title, body or base branch was changed.
+    #[serde(rename = "edited")]
+    Edited,
+
+    #[serde(rename = "ready_for_review")]
+    ReadyForReview,
+
+    #[serde(rename = "converted_to_draft")]
+    ConvertedToDraft,
+
+    /// Any action that is not handled, e.g. `labeled` or `review_requested`.
+    #[serde(other)]
+    Other,
 }
 
 #[allow(dead_code)]
@@ -58,6 +105,21 @@ pub(crate) struct IssueCommentPayload {
     pub comment: Comment,
 
     pub repository: Repository,
+    pub changes: Option<Changes>,
+
+    /// The user who triggered the event, i.e. the editor of an edited comment.
+    pub sender: Option<User>,
+}
+
+/// The previous values of the fields changed by an `edited` action.
+#[derive(Debug, Deserialize)]
+pub(crate) struct Changes {
+    pub body: Option<ChangedValue>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ChangedValue {
+    pub from: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -74,6 +136,85 @@ pub(crate) struct PullRequestReviewPayload {
     pub action: PullRequestReviewEventAction,
     pub review: PullRequestReview,
     pub pull_request: PullRequest,
+    pub repository: Repository,
+    pub changes: Option<Changes>,
+
+    /// The user who triggered the event, i.e. the editor of an edited comment.
+    pub sender: Option<User>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum PullRequestReviewCommentEventAction {
+    Created,
+    Edited,
+    Deleted,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct PullRequestReviewCommentPayload {
+    pub action: PullRequestReviewCommentEventAction,
+    pub comment: PullRequestReviewComment,
+    pub pull_request: PullRequest,
+    pub repository: Repository,
+    pub changes: Option<Changes>,
+
+    /// The user who triggered the event, i.e. the editor of an edited comment.
+    pub sender: Option<User>,
+}
+
+/// Where a comment that issued commands was written, so that it can be reacted to.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum CommandSource {
+    IssueComment { id: u64 },
+    Review { id: u64 },
+    ReviewComment { id: u64 },
+}
+
+/// The comment that issued a set of commands, and the issue or pull request it was written on.
+#[derive(Debug)]
+pub(crate) struct CommandContext<'a> {
+    pub repository: &'a Repository,
+
+    /// The number of the issue or pull request.
+    pub number: u64,
+
+    /// The author of the issue or pull request.
+    pub author: String,
+
+    /// The user who wrote the commands: the author of the comment, or whoever edited them in.
+    pub commenter: String,
+    pub source: CommandSource,
+}
+
+/// The login the commands in a comment by `author` are issued as. Anyone with write access can
+/// edit another user's comment, so commands are issued by whoever triggered the event rather
+/// than by the comment's author.
+fn commenter(sender: &Option<User>, author: &User) -> String {
+    match sender {
+        Some(s) => s.login.clone(),
+        None => author.login.clone(),
+    }
+}
+
+impl<'a> CommandContext<'a> {
+    fn for_pull_request(
+        repository: &'a Repository,
+        pull_request: &PullRequest,
+        commenter: &str,
+        source: CommandSource,
+    ) -> Self {
+        CommandContext {
+            repository,
+            number: pull_request.number,
+            author: match &pull_request.user {
+                Some(u) => u.login.clone(),
+                None => String::new(),
+            },
+            commenter: commenter.into(),
+            source,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -88,110 +229,674 @@ pub(crate) enum CheckSuiteEventAction {
 pub(crate) struct CheckSuitePayload {
     pub action: CheckSuiteEventAction,
     pub check_suite: CheckSuite,
+    pub repository: Repository,
 }
 
 #[derive(Debug, Deserialize)]
-#[serde(rename_all = "lowercase")]
+#[serde(rename_all = "snake_case")]
 pub(crate) enum CheckRunEventAction {
     Created,
+    Completed,
+    Rerequested,
+    RequestedAction,
 }
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct CheckRunPayload {
-    action: CheckRunEventAction,
-    id: u64,
-    name: String,
-    head_sha: String,
-    url: Url,
-    check_suite: CheckSuite,
+    pub action: CheckRunEventAction,
+    pub check_run: CheckRun,
+    pub repository: Repository,
+}
+
+/// A commit status reported through the legacy Status API.
+#[derive(Debug, Deserialize)]
+pub(crate) struct StatusPayload {
+    pub sha: String,
+    pub context: String,
+    pub state: StatusState,
+    pub repository: Repository,
 }
 
-const GITHUB_EVENT_KEY: &str = "X-GitHub-Event";
+pub(crate) const GITHUB_EVENT_KEY: &str = "X-GitHub-Event";
+pub(crate) const GITHUB_DELIVERY_KEY: &str = "X-GitHub-Delivery";
 const GITHUB_EVENT_ISSUE_COMMENT: &str = "issue_comment";
 const GITHUB_EVENT_PULL_REQUEST: &str = "pull_request";
 const GITHUB_EVENT_PULL_REQUEST_REVIEW: &str = "pull_request_review";
+const GITHUB_EVENT_PULL_REQUEST_REVIEW_COMMENT: &str = "pull_request_review_comment";
 const GITHUB_EVENT_CHECK_SUITE: &str = "check_suite";
 const GITHUB_EVENT_CHECK_RUN: &str = "check_run";
+const GITHUB_EVENT_STATUS: &str = "status";
 
-#[debug_handler]
-pub(crate) async fn post_github(
-    headers: HeaderMap,
-    State(state): State<AppState>,
-    body: String, // Json(payload): Json<EventPayload>,
-) {
+/// The subset of every webhook payload needed to find which repository sent it.
+#[derive(Debug, Deserialize)]
+struct DeliveryRepository {
+    repository: Option<Repository>,
+}
+
+/// Check the delivery's `X-Hub-Signature-256` header against the `secret` of the repository
+/// named in the payload. Returns the reason for rejection on failure.
+pub(crate) fn verify_delivery(headers: &HeaderMap, body: &str) -> Result<(), String> {
     let config = get_config();
-    let event_type = match headers.get(GITHUB_EVENT_KEY) {
-        Some(et) => et,
+
+    let repository = match serde_json::from_str::<DeliveryRepository>(body) {
+        Ok(DeliveryRepository {
+            repository: Some(r),
+        }) => r,
+        Ok(_) => return Err("Payload does not name a repository.".into()),
+        Err(e) => return Err(format!("Failed to read repository from payload. {e}")),
+    };
+
+    let owner = &repository.owner.login;
+
+    let repo_config = match config.repo(owner, &repository.name) {
+        Some(r) => r,
         None => {
-            error("No X-GitHub-Event key provided.".into(), Some(&config));
-            return;
+            return Err(format!(
+                "Repository {} is not configured.",
+                repository.full_name
+            ))
         }
     };
 
-    let payload = match event_type.to_str().unwrap() {
+    let signature = match headers.get(GITHUB_SIGNATURE_KEY) {
+        Some(s) => match s.to_str() {
+            Ok(s) => s,
+            Err(_) => return Err(format!("{GITHUB_SIGNATURE_KEY} is not valid ASCII.")),
+        },
+        None => return Err(format!("No {GITHUB_SIGNATURE_KEY} key provided.")),
+    };
+
+    if verify_signature(repo_config.secret(), body.as_bytes(), signature) {
+        Ok(())
+    } else {
+        Err(format!(
+            "Signature mismatch for delivery from {}.",
+            repository.full_name
+        ))
+    }
+}
+
+/// The response returned to Github for a webhook delivery, so that the outcome is visible in the
+/// repository's delivery log.
+#[derive(Debug)]
+pub(crate) enum WebhookResponse {
+    /// The delivery was parsed and dispatched.
+    Accepted,
+
+    /// The delivery was for an event type that is not handled.
+    Ignored,
+
+    /// The delivery could not be parsed.
+    BadRequest(String),
+
+    /// The delivery's signature could not be verified.
+    Unauthorized,
+
+    /// A delivery with the same `X-GitHub-Delivery` id has already been processed.
+    Duplicate,
+
+    /// Handling the delivery failed. The id is logged alongside the error.
+    InternalError { error_id: String },
+}
+
+impl IntoResponse for WebhookResponse {
+    fn into_response(self) -> Response {
+        match self {
+            WebhookResponse::Accepted => StatusCode::ACCEPTED.into_response(),
+            WebhookResponse::Ignored => StatusCode::NO_CONTENT.into_response(),
+            WebhookResponse::BadRequest(reason) => {
+                (StatusCode::BAD_REQUEST, reason).into_response()
+            }
+            WebhookResponse::Unauthorized => StatusCode::UNAUTHORIZED.into_response(),
+            WebhookResponse::Duplicate => {
+                (StatusCode::OK, "Delivery already processed.").into_response()
+            }
+            WebhookResponse::InternalError { error_id } => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Internal error. Error id: {error_id}"),
+            )
+                .into_response(),
+        }
+    }
+}
+
+impl WebhookResponse {
+    /// The outcome recorded against the stored delivery, with any reason or error id.
+    pub(crate) fn outcome(&self) -> (WebhookDeliveryOutcome, Option<String>) {
+        match self {
+            WebhookResponse::Accepted => (WebhookDeliveryOutcome::Accepted, None),
+            WebhookResponse::Ignored => (WebhookDeliveryOutcome::Ignored, None),
+            WebhookResponse::BadRequest(reason) => {
+                (WebhookDeliveryOutcome::BadRequest, Some(reason.clone()))
+            }
+            WebhookResponse::Unauthorized => (WebhookDeliveryOutcome::Unauthorized, None),
+            WebhookResponse::Duplicate => (WebhookDeliveryOutcome::Duplicate, None),
+            WebhookResponse::InternalError { error_id } => (
+                WebhookDeliveryOutcome::InternalError,
+                Some(error_id.clone()),
+            ),
+        }
+    }
+
+    /// Log `e` against a freshly generated error id and build the matching response.
+    fn internal_error(e: impl std::fmt::Display) -> Self {
+        let config = get_config();
+        let error_id = Utc::now().format("%Y%m%d%H%M%S%f").to_string();
+
+        error(
+            format!("Failed to handle webhook delivery (error id {error_id}). {e}"),
+            Some(&config),
+        );
+        WebhookResponse::InternalError { error_id }
+    }
+}
+
+/// Deserialize `body` according to the `X-GitHub-Event` type. Returns `Ok(None)` for event
+/// types that are not handled.
+pub(crate) fn parse_payload(
+    event_type: &str,
+    body: &str,
+) -> Result<Option<EventPayload>, serde_json::Error> {
+    let payload = match event_type {
         GITHUB_EVENT_ISSUE_COMMENT => {
-            EventPayload::IssueComment(serde_json::from_str::<IssueCommentPayload>(&body).unwrap())
+            EventPayload::IssueComment(serde_json::from_str::<IssueCommentPayload>(body)?)
         }
         GITHUB_EVENT_PULL_REQUEST => {
-            EventPayload::PullRequest(serde_json::from_str::<PullRequestPayload>(&body).unwrap())
+            EventPayload::PullRequest(serde_json::from_str::<PullRequestPayload>(body)?)
+        }
+        GITHUB_EVENT_CHECK_RUN => EventPayload::CheckRun(serde_json::from_str(body)?),
+        GITHUB_EVENT_CHECK_SUITE => EventPayload::CheckSuite(serde_json::from_str(body)?),
+        GITHUB_EVENT_STATUS => EventPayload::Status(serde_json::from_str(body)?),
+        GITHUB_EVENT_PULL_REQUEST_REVIEW => {
+            EventPayload::PullRequestReview(serde_json::from_str::<PullRequestReviewPayload>(body)?)
         }
-        GITHUB_EVENT_CHECK_RUN => EventPayload::CheckRun(serde_json::from_str(&body).unwrap()),
-        // GITHUB_EVENT_PULL_REQUEST_REVIEW => EventPayload::PullRequestReview(
-        //     serde_json::from_str::<PullRequestReviewPayload>(&body).unwrap(),
-        // ),
-        _ => {
-            error(format!("Unknown event {event_type:#?}"), Some(&config));
-            return;
+        GITHUB_EVENT_PULL_REQUEST_REVIEW_COMMENT => {
+            EventPayload::PullRequestReviewComment(serde_json::from_str(body)?)
         }
+        _ => return Ok(None),
     };
 
+    Ok(Some(payload))
+}
+
+/// Parse the commands added by an edit, if the edit changed the body to `body`.
+fn parse_edit(
+    bot_name: &str,
+    changes: &Option<Changes>,
+    body: &str,
+) -> Vec<Result<Command, ParseError>> {
+    match changes.as_ref().and_then(|c| c.body.as_ref()) {
+        Some(before) => parse_edited_command(bot_name, &before.from, body),
+        None => vec![],
+    }
+}
+
+/// Record the outcome of a command in the pull request's history.
+async fn record_outcome(cc: &CommandContext<'_>, command: &str, outcome: CommandOutcome) {
+    if let Err(e) = finish_command(cc, command, outcome).await {
+        error(
+            format!(
+                "Failed to record the outcome of `{command}` on #{}. {e}",
+                cc.number
+            ),
+            Some(&get_config()),
+        );
+    }
+}
+
+/// Run the commands parsed from a comment, replying to the parse errors and acknowledging the
+/// comment with a reaction once all of them have run.
+async fn run_commands(
+    app_user: &User,
+    cc: &CommandContext<'_>,
+    results: Vec<Result<Command, ParseError>>,
+) {
+    let config = get_config();
+    let mut errors = Vec::new();
+    let (mut accepted, mut started, mut rejected) = (false, false, false);
+
+    for result in results {
+        let command = match result {
+            Ok(command) => command,
+            Err(e) => {
+                errors.push(e);
+                continue;
+            }
+        };
+
+        // A command already run from this comment, e.g. by a redelivery, is not run again
+        let text = command.to_string();
+        match claim_command(cc, &text).await {
+            Ok(true) => {}
+            Ok(false) => {
+                info(
+                    format!(
+                        "Skipping `{text}` on #{}, it was already processed",
+                        cc.number
+                    ),
+                    Some(&config),
+                );
+                continue;
+            }
+            Err(e) => {
+                error(
+                    format!("Failed to record `{text}` on #{}. {e}", cc.number),
+                    Some(&config),
+                );
+                rejected = true;
+                continue;
+            }
+        }
+
+        if !authorize_command(cc, &command).await {
+            rejected = true;
+            record_outcome(cc, &text, CommandOutcome::Refused).await;
+            continue;
+        }
+
+        let starts_work = command.starts_work();
+        let done = match command {
+            Command::Approve => approve_pull(cc).await,
+            Command::ApproveAs { reviewer } => approve_pull_as(cc, &reviewer).await,
+            Command::Unapprove => unapprove_pull(cc).await,
+            Command::Delegate { user } => {
+                // delegate+ delegates to the pull request's author
+                let delegate = user.unwrap_or_else(|| cc.author.clone());
+                delegate_pull(cc, Some(delegate)).await
+            }
+            Command::RemoveDelegation => delegate_pull(cc, None).await,
+            Command::Ping => ping(cc).await,
+            Command::Help => help(cc, &app_user.login).await,
+            Command::Assign { user } => set_assignee(cc, user).await,
+            Command::RemoveAssignment => remove_assignee(cc).await,
+            Command::Priority { priority } => set_priority(cc, priority).await,
+            Command::Rollup { mode } => set_rollup(cc, mode).await,
+            Command::Squash { squash } => set_squash(cc, squash).await,
+            Command::TreeClosed { threshold } => set_tree_closed(cc, Some(threshold)).await,
+            Command::TreeOpen => set_tree_closed(cc, None).await,
+            Command::Retry => retry_pull(cc).await,
+            Command::Clean => clean_pull(cc).await,
+            Command::Try => request_try(cc).await,
+        };
+
+        match (done, starts_work) {
+            (true, true) => started = true,
+            (true, false) => accepted = true,
+            (false, _) => rejected = true,
+        }
+
+        let outcome = if done {
+            CommandOutcome::Succeeded
+        } else {
+            CommandOutcome::Failed
+        };
+        record_outcome(cc, &text, outcome).await;
+    }
+
+    if !errors.is_empty() {
+        rejected = true;
+        reply_parse_errors(cc, &app_user.login, &errors).await;
+    }
+
+    // Work that is still running implies the commands were accepted
+    if started {
+        react_to_comment(cc, config.started_reaction()).await;
+    } else if accepted {
+        react_to_comment(cc, config.accepted_reaction()).await;
+    }
+    if rejected {
+        react_to_comment(cc, config.rejected_reaction()).await;
+    }
+}
+
+/// Whether a pull request event must save the pull request before acting on it. Besides being
+/// opened, a pull request the bot has not seen, e.g. one opened before the bot was installed, is
+/// saved by the first event it receives so that the event's changes can be applied to it.
+fn saves_pull_request(action: &PullRequestEventAction, saved: bool) -> bool {
+    !saved && !matches!(action, PullRequestEventAction::Other)
+}
+
+/// Run the actions for a parsed webhook payload.
+pub(crate) async fn dispatch(app_user: &User, payload: EventPayload) -> Result<(), DbErr> {
     match payload {
         EventPayload::IssueComment(ic) => {
-            if let Some(comment_body) = &ic.comment.body {
-                let commands = parse_command(&state.app_user.login, &comment_body);
-
-                for command in commands {
-                    match command {
-                        Command::Approve => approve_pull(&ic).await,
-                        Command::Ping => ping(&ic).await,
-                        Command::Assign { user } => set_assignee(&ic, user).await,
-                        Command::RemoveAssignment => remove_assignee(&ic).await,
-                        _ => {}
-                    }
+            let results = match (&ic.action, &ic.comment.body) {
+                (IssueCommentEventAction::Created, Some(body)) => {
+                    parse_command(&app_user.login, body)
                 }
-            }
+                (IssueCommentEventAction::Edited, Some(body)) => {
+                    parse_edit(&app_user.login, &ic.changes, body)
+                }
+                _ => vec![],
+            };
+
+            let cc = CommandContext {
+                repository: &ic.repository,
+                number: ic.issue.number,
+                author: ic.issue.user.login.clone(),
+                commenter: commenter(&ic.sender, &ic.comment.user),
+                source: CommandSource::IssueComment { id: ic.comment.id },
+            };
+            run_commands(app_user, &cc, results).await;
         }
         EventPayload::PullRequest(PullRequestPayload {
             action,
             number,
             pull_request,
             repository,
-        }) => match action {
-            PullRequestEventAction::Opened => {
-                save_pull_to_db(pull_request, repository).await.unwrap()
+        }) => {
+            let saved = match action {
+                PullRequestEventAction::Other => true,
+                _ => is_pull_request_saved(pull_request.id).await?,
+            };
+            if saves_pull_request(&action, saved) {
+                save_pull_to_db(&pull_request, &repository).await?;
             }
-            PullRequestEventAction::Closed => {
-                if let Some(_) = pull_request.merged_at {
-                    set_pull_request_status(pull_request.id, PullRequestStatus::Merged)
-                        .await
-                        .unwrap()
-                } else {
-                    set_pull_request_status(pull_request.id, PullRequestStatus::Closed)
-                        .await
-                        .unwrap()
+
+            match action {
+                PullRequestEventAction::Opened => {}
+                PullRequestEventAction::Closed => {
+                    if let Some(_) = pull_request.merged_at {
+                        set_pull_request_status(pull_request.id, PullRequestStatus::Merged).await?
+                    } else {
+                        set_pull_request_closed(pull_request.id).await?
+                    }
+                }
+                PullRequestEventAction::Synchronize => {
+                    let head_sha = pull_request.head.sha;
+                    if set_pull_request_head(pull_request.id, head_sha.clone()).await? {
+                        comment_approval_reset(&repository, number, &head_sha).await;
+                        info(
+                            format!(
+                                "Approval for pull request #{number} in {} reset by a push",
+                                repository.full_name
+                            ),
+                            Some(&get_config()),
+                        );
+                    }
                 }
+                PullRequestEventAction::Reopened => {
+                    set_pull_request_status(pull_request.id, PullRequestStatus::Pending).await?
+                }
+                PullRequestEventAction::Edited => {
+                    set_pull_request_base(pull_request.id, pull_request.base.label).await?
+                }
+                PullRequestEventAction::ReadyForReview => {
+                    set_pull_request_draft(pull_request.id, false).await?
+                }
+                PullRequestEventAction::ConvertedToDraft => {
+                    set_pull_request_draft(pull_request.id, true).await?
+                }
+                PullRequestEventAction::Other => {}
             }
-        },
+        }
+
+        EventPayload::PullRequestReview(review) => {
+            let reviewed = handle_pull_request_review(&review).await;
+
+            let mut results = match (&review.action, &review.review.body) {
+                (PullRequestReviewEventAction::Submitted, Some(body)) => {
+                    parse_command(&app_user.login, body)
+                }
+                (PullRequestReviewEventAction::Edited, Some(body)) => {
+                    parse_edit(&app_user.login, &review.changes, body)
+                }
+                _ => vec![],
+            };
+
+            // The review's state already decided the approval
+            if reviewed {
+                results.retain(|r| {
+                    !matches!(
+                        r,
+                        Ok(Command::Approve | Command::ApproveAs { .. } | Command::Unapprove)
+                    )
+                });
+            }
+
+            let cc = CommandContext::for_pull_request(
+                &review.repository,
+                &review.pull_request,
+                &commenter(&review.sender, &review.review.user),
+                CommandSource::Review {
+                    id: review.review.id,
+                },
+            );
+            run_commands(app_user, &cc, results).await;
+        }
+
+        EventPayload::PullRequestReviewComment(rc) => {
+            let results = match rc.action {
+                PullRequestReviewCommentEventAction::Created => {
+                    parse_command(&app_user.login, &rc.comment.body)
+                }
+                PullRequestReviewCommentEventAction::Edited => {
+                    parse_edit(&app_user.login, &rc.changes, &rc.comment.body)
+                }
+                PullRequestReviewCommentEventAction::Deleted => vec![],
+            };
+
+            let cc = CommandContext::for_pull_request(
+                &rc.repository,
+                &rc.pull_request,
+                &commenter(&rc.sender, &rc.comment.user),
+                CommandSource::ReviewComment { id: rc.comment.id },
+            );
+            run_commands(app_user, &cc, results).await;
+        }
 
         EventPayload::CheckRun(CheckRunPayload {
             action,
-            id,
-            name,
-            head_sha,
-            url,
+            check_run,
+            repository,
+        }) => match (action, &check_run.conclusion) {
+            (CheckRunEventAction::Completed, Some(conclusion)) => {
+                record_check_run(
+                    &repository.full_name,
+                    &check_run.head_sha,
+                    &check_run.name,
+                    conclusion.into(),
+                )
+                .await?
+            }
+            _ => {}
+        },
+
+        // A check suite is recorded under the name of the app that ran it, so that a repository
+        // can wait on e.g. "GitHub Actions" as a whole rather than on each check run.
+        EventPayload::CheckSuite(CheckSuitePayload {
+            action,
             check_suite,
-        }) => match action {
-            CheckRunEventAction::Created => {}
+            repository,
+        }) => match (action, &check_suite.conclusion, &check_suite.app) {
+            (CheckSuiteEventAction::Completed, Some(conclusion), Some(app)) => {
+                record_check_run(
+                    &repository.full_name,
+                    &check_suite.head_sha,
+                    &app.name,
+                    conclusion.into(),
+                )
+                .await?
+            }
+            _ => {}
         },
+
+        EventPayload::Status(StatusPayload {
+            sha,
+            context,
+            state,
+            repository,
+        }) => record_commit_status(&repository.full_name, &sha, &context, (&state).into()).await?,
+    }
+
+    Ok(())
+}
+
+#[debug_handler]
+pub(crate) async fn post_github(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    body: String, // Json(payload): Json<EventPayload>,
+) -> WebhookResponse {
+    let config = get_config();
+    let response = handle_delivery(&headers, &state.app_user, &body).await;
+
+    if let Err(e) = store_delivery(&headers, &body, &response).await {
+        error(
+            format!("Failed to store webhook delivery. {e}"),
+            Some(&config),
+        );
+    }
+
+    response
+}
+
+async fn handle_delivery(headers: &HeaderMap, app_user: &User, body: &str) -> WebhookResponse {
+    let config = get_config();
+    let event_type = match headers.get(GITHUB_EVENT_KEY).map(|et| et.to_str()) {
+        Some(Ok(et)) => et,
+        Some(Err(_)) => {
+            error("X-GitHub-Event is not valid ASCII.".into(), Some(&config));
+            return WebhookResponse::BadRequest("X-GitHub-Event is not valid ASCII.".into());
+        }
+        None => {
+            error("No X-GitHub-Event key provided.".into(), Some(&config));
+            return WebhookResponse::BadRequest("No X-GitHub-Event key provided.".into());
+        }
+    };
+
+    if let Err(e) = verify_delivery(headers, body) {
+        error(format!("Rejected webhook delivery. {e}"), Some(&config));
+        return WebhookResponse::Unauthorized;
+    }
+
+    let delivery_id = match headers.get(GITHUB_DELIVERY_KEY).map(|d| d.to_str()) {
+        Some(Ok(d)) => d,
+        Some(Err(_)) | None => {
+            error(
+                "No valid X-GitHub-Delivery key provided.".into(),
+                Some(&config),
+            );
+            return WebhookResponse::BadRequest("No valid X-GitHub-Delivery key provided.".into());
+        }
+    };
+
+    match claim_delivery(delivery_id).await {
+        Ok(true) => {}
+        Ok(false) => {
+            info(
+                format!("Skipping already processed delivery {delivery_id}"),
+                Some(&config),
+            );
+            return WebhookResponse::Duplicate;
+        }
+        Err(e) => return WebhookResponse::internal_error(e),
+    }
+
+    let response = match parse_payload(event_type, body) {
+        Ok(Some(payload)) => match dispatch(app_user, payload).await {
+            Ok(_) => WebhookResponse::Accepted,
+            Err(e) => WebhookResponse::internal_error(e),
+        },
+        Ok(None) => {
+            info(format!("Ignoring event {event_type}"), Some(&config));
+            WebhookResponse::Ignored
+        }
+        Err(e) => {
+            error(
+                format!("Failed to parse {event_type} payload. {e}"),
+                Some(&config),
+            );
+            WebhookResponse::BadRequest(format!("Failed to parse {event_type} payload."))
+        }
+    };
+
+    // Let Github's redelivery retry anything that was not handled successfully.
+    if let WebhookResponse::BadRequest(_) | WebhookResponse::InternalError { .. } = response {
+        if let Err(e) = release_delivery(delivery_id).await {
+            error(
+                format!("Failed to release delivery {delivery_id}. {e}"),
+                Some(&config),
+            );
+        }
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_payload, saves_pull_request, PullRequestEventAction};
+    use serde_json::{json, Value};
+
+    fn user(login: &str) -> Value {
+        let url = format!("https://api.github.com/users/{login}");
+        json!({
+            "login": login, "id": 1, "node_id": "U_1", "avatar_url": url, "gravatar_id": "",
+            "url": url, "html_url": url, "followers_url": url, "following_url": url,
+            "gists_url": url, "starred_url": url, "subscriptions_url": url,
+            "organizations_url": url, "repos_url": url, "events_url": url,
+            "received_events_url": url, "type": "User", "site_admin": false
+        })
+    }
+
+    fn synchronize_payload() -> Value {
+        json!({
+            "action": "synchronize",
+            "number": 1,
+            "pull_request": {
+                "id": 10, "number": 1, "user": user("bob"),
+                "head": { "label": "bob:fix", "ref": "fix", "sha": "abc123" },
+                "base": { "label": "xva-lang:main", "ref": "main", "sha": "def456" }
+            },
+            "repository": {
+                "id": 100, "name": "yad", "full_name": "xva-lang/yad", "owner": user("xva-lang")
+            }
+        })
+    }
+
+    #[test]
+    fn parse_pull_request_payload() {
+        let payload = synchronize_payload().to_string();
+        assert!(matches!(
+            parse_payload("pull_request", &payload),
+            Ok(Some(_))
+        ));
+    }
+
+    #[test]
+    fn missing_fields_are_rejected() {
+        let mut payload = synchronize_payload();
+        payload["repository"]
+            .as_object_mut()
+            .unwrap()
+            .remove("owner");
+        assert!(parse_payload("pull_request", &payload.to_string()).is_err());
+
+        let mut payload = synchronize_payload();
+        payload["pull_request"]["head"]
+            .as_object_mut()
+            .unwrap()
+            .remove("label");
+        assert!(parse_payload("pull_request", &payload.to_string()).is_err());
+    }
+
+    #[test]
+    fn opened_pull_request_is_saved_once() {
+        assert!(saves_pull_request(&PullRequestEventAction::Opened, false));
+        assert!(!saves_pull_request(&PullRequestEventAction::Opened, true));
+    }
+
+    #[test]
+    fn synchronize_saves_unknown_pull_request() {
+        assert!(saves_pull_request(
+            &PullRequestEventAction::Synchronize,
+            false
+        ));
+        assert!(!saves_pull_request(
+            &PullRequestEventAction::Synchronize,
+            true
+        ));
+        assert!(!saves_pull_request(&PullRequestEventAction::Other, false));
     }
 }
@@ -0,0 +1,67 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub(crate) const GITHUB_SIGNATURE_KEY: &str = "X-Hub-Signature-256";
+const SIGNATURE_PREFIX: &str = "sha256=";
+
+/// Verify the `X-Hub-Signature-256` header of a webhook delivery against the repository's
+/// configured `secret`.
+///
+/// Github signs the raw request body with HMAC-SHA256 and sends the hex digest prefixed with
+/// `sha256=`. The comparison is done in constant time by [`Mac::verify_slice`].
+pub(crate) fn verify_signature(secret: &str, body: &[u8], signature: &str) -> bool {
+    let digest = match signature.strip_prefix(SIGNATURE_PREFIX) {
+        Some(d) => d,
+        None => return false,
+    };
+
+    let digest = match hex::decode(digest) {
+        Ok(d) => d,
+        Err(_) => return false,
+    };
+
+    let mut mac = match HmacSha256::new_from_slice(secret.as_bytes()) {
+        Ok(m) => m,
+        Err(_) => return false,
+    };
+    mac.update(body);
+    mac.verify_slice(&digest).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::verify_signature;
+
+    // Example values from Github's "Validating webhook deliveries" documentation.
+    const SECRET: &str = "It's a Secret to Everybody";
+    const BODY: &str = "Hello, World!";
+    const SIGNATURE: &str =
+        "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+
+    #[test]
+    fn valid_signature() {
+        assert!(verify_signature(SECRET, BODY.as_bytes(), SIGNATURE));
+    }
+
+    #[test]
+    fn wrong_secret() {
+        assert!(!verify_signature(
+            "not the secret",
+            BODY.as_bytes(),
+            SIGNATURE
+        ));
+    }
+
+    #[test]
+    fn tampered_body() {
+        assert!(!verify_signature(SECRET, b"Hello, World?", SIGNATURE));
+    }
+
+    #[test]
+    fn malformed_signature() {
+        assert!(!verify_signature(SECRET, BODY.as_bytes(), "sha1=abcdef"));
+        assert!(!verify_signature(SECRET, BODY.as_bytes(), "sha256=not-hex"));
+    }
+}
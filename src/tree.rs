@@ -0,0 +1,56 @@
+use chrono::Utc;
+use entity::tree_states::{
+    ActiveModel as TreeStateActiveModel, Column as TreeStatesColumn, Entity as TreeStatesEntity,
+};
+use sea_orm::{sea_query::OnConflict, DbErr, EntityTrait, Set};
+
+use crate::{config::Config, db::get_db};
+
+/// Record the repository's tree state as set by `treeclosed=N` or `treeclosed-`. A `threshold`
+/// of `None` opens the tree.
+pub(crate) async fn set_tree_state(
+    repository: &str,
+    threshold: Option<i32>,
+    updated_by: &str,
+) -> Result<(), DbErr> {
+    let row = TreeStateActiveModel {
+        repository: Set(repository.into()),
+        threshold: Set(threshold),
+        updated_by: Set(updated_by.into()),
+        updated_at: Set(Utc::now()),
+    };
+
+    TreeStatesEntity::insert(row)
+        .on_conflict(
+            OnConflict::column(TreeStatesColumn::Repository)
+                .update_columns([
+                    TreeStatesColumn::Threshold,
+                    TreeStatesColumn::UpdatedBy,
+                    TreeStatesColumn::UpdatedAt,
+                ])
+                .to_owned(),
+        )
+        .exec_without_returning(&get_db().await?)
+        .await?;
+    Ok(())
+}
+
+/// The lowest priority a pull request in the `owner/repo` repository must have to be merged, or
+/// `None` if the tree is open. State set by the tree commands takes precedence over the
+/// repository's configuration.
+pub(crate) async fn get_tree_threshold(
+    config: &Config,
+    repository: &str,
+) -> Result<Option<i32>, DbErr> {
+    if let Some(state) = TreeStatesEntity::find_by_id(repository.to_string())
+        .one(&get_db().await?)
+        .await?
+    {
+        return Ok(state.threshold);
+    }
+
+    Ok(match repository.split_once("/") {
+        Some((owner, repo)) => config.repo(owner, repo).and_then(|r| r.merge_threshold()),
+        None => None,
+    })
+}